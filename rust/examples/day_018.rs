@@ -3,7 +3,7 @@
     AST Interpreter - craftinginterpreters
 
     Current:
-    http://craftinginterpreters.com/scanning.html
+    http://craftinginterpreters.com/classes.html
 */
 
 use std::{
@@ -14,17 +14,38 @@ use std::{
 use one_hundred_days_of_code::ast::{
     tokens::{Token},
     scanner::{Scanner},
+    parser::{Parser},
+    resolver::{Resolver},
+    error::{ErrorHandler},
+    interpreter::{Interpreter},
 };
 
 // Execution of code
-fn run(source: String) -> Result<(), String>
+fn run(source: String, interpreter: &mut Interpreter) -> Result<(), String>
 {
     let mut scanner = Scanner::with_source(source);
-    let tokens: &Vec<Token> = scanner.scan_tokens().unwrap();
+    let tokens: Vec<Token> = scanner.scan_tokens()?.clone();
 
-    for tok in tokens
+    let mut errors = ErrorHandler::new();
+    let statements = Parser::new(tokens, &mut errors).parse();
+
+    if errors.had_error()
+    {
+        return Err(String::from("Failed to parse"));
+    }
+
+    Resolver::new(&mut errors).resolve(&statements);
+
+    if errors.had_error()
+    {
+        return Err(String::from("Failed to resolve"));
+    }
+
+    interpreter.interpret(&statements, &mut errors);
+
+    if errors.had_runtime_error()
     {
-        println!("Token ({:?})", tok.to_string());
+        return Err(String::from("Runtime error"));
     }
 
     Ok(())
@@ -33,10 +54,12 @@ fn run(source: String) -> Result<(), String>
 // Read a file and run
 fn run_file(file_path: String) -> Result<(), String>
 {
+    let mut interpreter = Interpreter::new();
+
     // Read source code from file
     match fs::read_to_string(file_path)
     {
-        Ok(s) => run(s),
+        Ok(s) => run(s, &mut interpreter),
         Err(e) => Err(format!("Error: {}", e)),
     }
 }
@@ -47,19 +70,24 @@ fn run_prompt() -> Result<(), String>
     println!("=== Rusty Lox Repl ===");
 
     let mut code = String::new();
+    let mut interpreter = Interpreter::new();
 
     loop
     {
         print!("> ");
         
         io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut code).unwrap();
 
-        match run(code.clone())
+        // Stop at end of input
+        code.clear();
+        if io::stdin().read_line(&mut code).unwrap() == 0
         {
-            Ok(_) => {},
-            Err(e) => return Err(format!("Error: {}", e)),
+            println!();
+            return Ok(());
         }
+
+        // Errors have already been reported, keep going with the next line
+        let _ = run(code.clone(), &mut interpreter);
     }
 }
 
@@ -79,4 +107,4 @@ fn main() -> Result<(), String>
     };
 
     status
-}
\ No newline at end of file
+}
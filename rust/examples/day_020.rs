@@ -4,7 +4,10 @@
 */
 
 use one_hundred_days_of_code::bytecode::{
-    vm::{VM, InterpretResult},
+    chunk::Chunk,
+    compiler::CompileError,
+    serialize::MAGIC,
+    vm::{VM, InterpretResult, RuntimeError},
 };
 use std::{
     fs,
@@ -12,6 +15,30 @@ use std::{
     io::{self, Write},
 };
 
+fn report_errors(errors: &[CompileError], source: &str)
+{
+    for error in errors
+    {
+        eprintln!("{}\n{}", error, error.excerpt(source));
+    }
+}
+
+// Bytecode files carry no source, so there may be nothing to point at
+fn report_runtime_error(error: &RuntimeError, source: Option<&str>)
+{
+    eprintln!("{}", error.message);
+
+    if let Some(source) = source
+    {
+        eprintln!("{}", error.excerpt(source));
+    }
+
+    for frame in &error.trace
+    {
+        eprintln!("{}", frame);
+    }
+}
+
 // Read a file and run
 fn run_file(file_path: String) -> Result<(), String>
 {
@@ -20,23 +47,66 @@ fn run_file(file_path: String) -> Result<(), String>
 
     use InterpretResult::*;
 
-    // Read source code from file
-    match fs::read_to_string(file_path)
+    let bytes = match fs::read(file_path)
     {
-        Ok(s) => {
-            match vm.interpret(s)
-            {
-                Okay => {},
-                _ => return Err("Error occured".to_string()),
-            }
-        }
+        Ok(bytes) => bytes,
         Err(e) => return Err(format!("Error: {}", e)),
+    };
+
+    // Compiled bytecode files are run as-is, anything else is treated as source
+    let (result, source) = if bytes.starts_with(MAGIC)
+    {
+        match Chunk::read_from(&mut bytes.as_slice(), &mut vm)
+        {
+            Ok(chunk) => (vm.interpret_chunk(chunk), None),
+            Err(e) => return Err(format!("Error: {}", e)),
+        }
+    }
+    else
+    {
+        match String::from_utf8(bytes)
+        {
+            Ok(s) => (vm.interpret(s.clone()), Some(s)),
+            Err(e) => return Err(format!("Error: {}", e)),
+        }
+    };
+
+    match result
+    {
+        Okay => {},
+        CompilerError(errors) => {
+            report_errors(&errors, source.as_deref().unwrap_or_default());
+            return Err("Error occured".to_string());
+        }
+        RuntimeError(error) => {
+            report_runtime_error(&error, source.as_deref());
+            return Err("Error occured".to_string());
+        }
     }
 
     vm.free();
     Ok(())
 }
 
+// Compile a source file to bytecode, without running it
+fn compile_file(file_path: String, out_path: String) -> Result<(), String>
+{
+    let mut vm = VM::new();
+
+    let source = fs::read_to_string(file_path).map_err(|e| format!("Error: {}", e))?;
+    let chunk = match vm.compile(source.clone())
+    {
+        Ok(chunk) => chunk,
+        Err(errors) => {
+            report_errors(&errors, &source);
+            return Err("Error occured".to_string());
+        }
+    };
+
+    let mut file = fs::File::create(out_path).map_err(|e| format!("Error: {}", e))?;
+    chunk.write_to(&mut file).map_err(|e| format!("Error: {}", e))
+}
+
 // Run as a prompt
 fn run_prompt() -> Result<(), String>
 {
@@ -53,7 +123,12 @@ fn run_prompt() -> Result<(), String>
         print!("> ");
         
         io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut code).unwrap();
+        // Stop at end of input
+        if io::stdin().read_line(&mut code).unwrap() == 0
+        {
+            println!();
+            return Ok(());
+        }
 
         // Cleanup string
         code = code.trim_end().to_string();
@@ -68,7 +143,8 @@ fn run_prompt() -> Result<(), String>
         match vm.interpret(code.clone())
         {
             Okay => {},
-            CompilerError | RuntimeError => return Err("Error occured".to_string()),
+            CompilerError(errors) => report_errors(&errors, &code),
+            RuntimeError(error) => report_runtime_error(&error, Some(&code)),
         }
 
         code.clear();
@@ -79,12 +155,18 @@ fn main()
 {
     let args: Vec<_> = env::args().collect();
 
-    let _ = match args.len()
+    let result = match args.len()
     {
         1 => run_prompt(),
         2 => run_file(args[1].clone()),
+        3 => compile_file(args[1].clone(), args[2].clone()),
         _ => {
-            panic!("Usage: rlox [path]");
+            panic!("Usage: rlox [path] [bytecode out]");
         }
     };
+
+    if let Err(e) = result
+    {
+        eprintln!("{}", e);
+    }
 }
\ No newline at end of file
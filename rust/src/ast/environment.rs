@@ -0,0 +1,109 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::{
+    error::RuntimeError,
+    tokens::Token,
+    value::Value,
+};
+
+// One scope worth of variables, chained to the scope it was opened in
+#[derive(Default)]
+pub struct Environment
+{
+    values: HashMap<String, Value>,
+    enclosing: Option<Rc<RefCell<Environment>>>,
+}
+
+impl Environment
+{
+    pub fn new() -> Environment
+    {
+        Environment
+        {
+            values: HashMap::new(),
+            enclosing: None,
+        }
+    }
+
+    pub fn with_enclosing(enclosing: Rc<RefCell<Environment>>) -> Environment
+    {
+        Environment
+        {
+            values: HashMap::new(),
+            enclosing: Some(enclosing),
+        }
+    }
+
+    // Redefining a name in the same scope just replaces it
+    pub fn define(&mut self, name: &str, value: Value)
+    {
+        self.values.insert(name.to_string(), value);
+    }
+
+    pub fn get(&self, name: &Token) -> Result<Value, RuntimeError>
+    {
+        match self.lookup(&name.lexeme)
+        {
+            Some(value) => Ok(value),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme))),
+        }
+    }
+
+    // Searches every scope outwards, without an error if it isn't there
+    pub fn lookup(&self, name: &str) -> Option<Value>
+    {
+        if let Some(value) = self.values.get(name)
+        {
+            return Some(value.clone());
+        }
+
+        self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().lookup(name))
+    }
+
+    // The resolver worked out which scope a local lives in, so skip straight to it
+    pub fn get_at(&self, depth: usize, name: &str) -> Option<Value>
+    {
+        match depth
+        {
+            0 => self.values.get(name).cloned(),
+            _ => self.enclosing.as_ref().and_then(|enclosing| enclosing.borrow().get_at(depth - 1, name)),
+        }
+    }
+
+    pub fn assign_at(&mut self, depth: usize, name: &Token, value: Value) -> Result<(), RuntimeError>
+    {
+        if depth == 0
+        {
+            if let Some(slot) = self.values.get_mut(&name.lexeme)
+            {
+                *slot = value;
+                return Ok(());
+            }
+        }
+
+        match &self.enclosing
+        {
+            Some(enclosing) if depth > 0 => enclosing.borrow_mut().assign_at(depth - 1, name, value),
+            _ => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme))),
+        }
+    }
+
+    pub fn assign(&mut self, name: &Token, value: Value) -> Result<(), RuntimeError>
+    {
+        if let Some(slot) = self.values.get_mut(&name.lexeme)
+        {
+            *slot = value;
+            return Ok(());
+        }
+
+        match &self.enclosing
+        {
+            Some(enclosing) => enclosing.borrow_mut().assign(name, value),
+            None => Err(RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme))),
+        }
+    }
+}
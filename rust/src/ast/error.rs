@@ -1,6 +1,32 @@
+use super::tokens::Token;
+
+// Raised while running, the token is where in the source it happened
+#[derive(Debug, Clone)]
+pub struct RuntimeError
+{
+    pub token: Token,
+    pub message: String,
+}
+
+impl RuntimeError
+{
+    pub fn new(token: &Token, message: String) -> RuntimeError
+    {
+        RuntimeError
+        {
+            token: token.clone(),
+            message,
+        }
+    }
+}
+
 pub struct ErrorHandler
 {
     had_error: bool,
+    // The error that stopped the last run, if any
+    last_runtime_error: Option<RuntimeError>,
+    // Everything reported so far, so callers can show or inspect them later
+    pub messages: Vec<String>,
 }
 
 impl ErrorHandler 
@@ -10,17 +36,71 @@ impl ErrorHandler
         ErrorHandler
         {
             had_error: false,
+            last_runtime_error: None,
+            messages: Vec::new(),
         }
     }
 
     pub fn report(&mut self, line: i32, found_at: String, message: String)
     {
-        println!("[line {}] Error {}: {}", line, found_at, message);
+        let message = match found_at.is_empty()
+        {
+            true => format!("[line {}] Error: {}", line, message),
+            false => format!("[line {}] Error {}: {}", line, found_at, message),
+        };
+
+        eprintln!("{}", message);
+        self.messages.push(message);
         self.had_error = true;
     }
 
     pub fn error(&mut self, line: i32, message: String)
     {
         self.report(line, String::new(), message);
-    }   
-}
\ No newline at end of file
+    }
+
+    // Worth pointing out, but doesn't stop the code from running
+    pub fn warning(&mut self, line: i32, found_at: String, message: String)
+    {
+        let message = match found_at.is_empty()
+        {
+            true => format!("[line {}] Warning: {}", line, message),
+            false => format!("[line {}] Warning {}: {}", line, found_at, message),
+        };
+
+        eprintln!("{}", message);
+        self.messages.push(message);
+    }
+
+    pub fn runtime_error(&mut self, error: RuntimeError)
+    {
+        let message = format!("{}\n[line {}]", error.message, error.token.line);
+
+        eprintln!("{}", message);
+        self.messages.push(message);
+        self.last_runtime_error = Some(error);
+    }
+
+    pub fn had_error(&self) -> bool
+    {
+        self.had_error
+    }
+
+    pub fn had_runtime_error(&self) -> bool
+    {
+        self.last_runtime_error.is_some()
+    }
+
+    pub fn last_runtime_error(&self) -> Option<&RuntimeError>
+    {
+        self.last_runtime_error.as_ref()
+    }
+
+    // The REPL keeps going after a bad line
+    pub fn reset(&mut self)
+    {
+        self.had_error = false;
+        self.last_runtime_error = None;
+        self.messages.clear();
+    }
+}
@@ -0,0 +1,36 @@
+use std::cell::Cell;
+
+use super::tokens::Token;
+
+// How many scopes out a name was declared, filled in by the resolver.
+// None means it is a global.
+pub type Depth = Cell<Option<usize>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal
+{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum Expr
+{
+    Assign { name: Token, value: Box<Expr>, depth: Depth },
+    Binary { left: Box<Expr>, operator: Token, right: Box<Expr> },
+    // The closing paren is kept to report errors against
+    Call { callee: Box<Expr>, paren: Token, arguments: Vec<Expr> },
+    Get { object: Box<Expr>, name: Token },
+    Grouping { expression: Box<Expr> },
+    Literal { value: Literal },
+    // Like Binary, but and/or only evaluate the right side when needed
+    Logical { left: Box<Expr>, operator: Token, right: Box<Expr> },
+    Set { object: Box<Expr>, name: Token, value: Box<Expr> },
+    // parent.method
+    Super { keyword: Token, method: Token, depth: Depth },
+    This { keyword: Token, depth: Depth },
+    Unary { operator: Token, right: Box<Expr> },
+    Variable { name: Token, depth: Depth },
+}
@@ -0,0 +1,544 @@
+use super::{
+    error::ErrorHandler,
+    expr::{Expr, Literal},
+    parser::Parser,
+    scanner::Scanner,
+    stmt::{Function, Stmt},
+    tokens::Token,
+};
+
+const INDENT: &str = "    ";
+
+// Reparses the source and prints it back in the project's own style: Allman
+// braces, four space indents, one statement per line. Comments are kept and
+// so is a single blank line wherever the source had one or more.
+pub fn format(source: &str) -> Result<String, String>
+{
+    let mut scanner = Scanner::with_source(source.to_string());
+    let tokens = scanner.scan_tokens()?.clone();
+
+    let mut errors = ErrorHandler::new();
+    let statements = Parser::new(tokens.clone(), &mut errors).parse();
+
+    if errors.had_error()
+    {
+        return Err(String::from("Failed to parse"));
+    }
+
+    let mut formatter = Formatter::new(&tokens, scanner.comments());
+    formatter.statements(&statements);
+    Ok(formatter.finish())
+}
+
+// The tree is printed back as the same tokens in the same order, only the
+// whitespace changes. So walking the source tokens alongside tells us where
+// each comment sat without the tree having to remember it.
+struct Formatter<'a>
+{
+    tokens: &'a [Token],
+    comments: &'a [Token],
+    next_token: usize,
+    next_comment: usize,
+    lines: Vec<String>,
+    line: String,
+    // The line is finished, but stays open in case a trailing comment follows
+    line_done: bool,
+    indent: usize,
+    // Last source line printed, to spot blank lines worth keeping
+    last_line: i32,
+    // Only the start of a statement or comment may have a blank line before it
+    statement_start: bool,
+    after_open_brace: bool,
+}
+
+impl<'a> Formatter<'a>
+{
+    fn new(tokens: &'a [Token], comments: &'a [Token]) -> Formatter<'a>
+    {
+        Formatter
+        {
+            tokens,
+            comments,
+            next_token: 0,
+            next_comment: 0,
+            lines: Vec::new(),
+            line: String::new(),
+            line_done: false,
+            indent: 0,
+            last_line: 1,
+            statement_start: false,
+            after_open_brace: false,
+        }
+    }
+
+    fn finish(mut self) -> String
+    {
+        self.flush_comments(None);
+
+        if !self.line.is_empty()
+        {
+            self.commit();
+        }
+
+        let mut output = self.lines.join("\n");
+        if !output.is_empty()
+        {
+            output.push('\n');
+        }
+        output
+    }
+
+    fn statements(&mut self, statements: &[Stmt])
+    {
+        for statement in statements
+        {
+            self.statement(statement);
+        }
+    }
+
+    // Every statement starts on a line of its own
+    fn statement(&mut self, statement: &Stmt)
+    {
+        self.end_line();
+        self.statement_start = true;
+        self.write_statement(statement);
+        self.end_line();
+    }
+
+    // Without the line breaks around it, so for loops can use it inline
+    fn write_statement(&mut self, statement: &Stmt)
+    {
+        match statement
+        {
+            Stmt::Block { statements } => self.block(statements),
+            Stmt::Class { name, superclass, methods } =>
+            {
+                self.token("class");
+                self.space();
+                self.token(&name.lexeme);
+
+                if let Some(superclass) = superclass
+                {
+                    self.space();
+                    self.token("<");
+                    self.space();
+                    self.expression(superclass);
+                }
+
+                self.open_brace();
+
+                for method in methods
+                {
+                    self.end_line();
+                    self.statement_start = true;
+                    self.function(method);
+                }
+
+                self.close_brace();
+            }
+            Stmt::Expression { expression } =>
+            {
+                self.expression(expression);
+                self.token(";");
+            }
+            Stmt::Function { function } =>
+            {
+                self.token("fn");
+                self.space();
+                self.function(function);
+            }
+            Stmt::If { condition, then_branch, else_branch } =>
+            {
+                self.token("if");
+                self.space();
+                self.token("(");
+                self.expression(condition);
+                self.token(")");
+                self.body(then_branch);
+
+                if let Some(else_branch) = else_branch
+                {
+                    self.end_line();
+                    self.token("else");
+
+                    // Chains of else if stay flat
+                    match else_branch.as_ref()
+                    {
+                        Stmt::If { .. } =>
+                        {
+                            self.space();
+                            self.write_statement(else_branch);
+                        }
+                        _ => self.body(else_branch),
+                    }
+                }
+            }
+            Stmt::Print { expression } =>
+            {
+                self.token("print");
+                self.space();
+                self.expression(expression);
+                self.token(";");
+            }
+            Stmt::Return { value, .. } =>
+            {
+                self.token("return");
+
+                if let Some(value) = value
+                {
+                    self.space();
+                    self.expression(value);
+                }
+
+                self.token(";");
+            }
+            Stmt::Var { name, initializer } =>
+            {
+                self.token("let");
+                self.space();
+                self.token(&name.lexeme);
+
+                if let Some(initializer) = initializer
+                {
+                    self.space();
+                    self.token("=");
+                    self.space();
+                    self.expression(initializer);
+                }
+
+                self.token(";");
+            }
+            Stmt::While { condition, body } =>
+            {
+                self.token("while");
+                self.space();
+                self.token("(");
+                self.expression(condition);
+                self.token(")");
+                self.body(body);
+            }
+            Stmt::For { initializer, condition, increment, body } =>
+            {
+                self.token("for");
+                self.space();
+                self.token("(");
+
+                // The initializer brings its own semicolon
+                match initializer
+                {
+                    Some(initializer) => self.write_statement(initializer),
+                    None => self.token(";"),
+                }
+
+                if let Some(condition) = condition
+                {
+                    self.space();
+                    self.expression(condition);
+                }
+
+                self.token(";");
+
+                if let Some(increment) = increment
+                {
+                    self.space();
+                    self.expression(increment);
+                }
+
+                self.token(")");
+                self.body(body);
+            }
+        }
+    }
+
+    // Blocks line up with the statement that owns them, anything else is indented under it
+    fn body(&mut self, body: &Stmt)
+    {
+        match body
+        {
+            Stmt::Block { .. } => self.write_statement(body),
+            _ =>
+            {
+                self.indent += 1;
+                self.statement(body);
+                self.indent -= 1;
+            }
+        }
+    }
+
+    // Name, parameters and body. The caller writes 'fn' if it needs one
+    fn function(&mut self, function: &Function)
+    {
+        self.token(&function.name.lexeme);
+        self.token("(");
+
+        for (i, param) in function.params.iter().enumerate()
+        {
+            if i > 0
+            {
+                self.token(",");
+                self.space();
+            }
+
+            self.token(&param.lexeme);
+        }
+
+        self.token(")");
+        self.block(&function.body);
+    }
+
+    fn block(&mut self, statements: &[Stmt])
+    {
+        self.open_brace();
+        self.statements(statements);
+        self.close_brace();
+    }
+
+    fn open_brace(&mut self)
+    {
+        self.end_line();
+        self.token("{");
+        self.end_line();
+        self.indent += 1;
+        self.after_open_brace = true;
+    }
+
+    fn close_brace(&mut self)
+    {
+        // Comments before the brace belong inside the block
+        let position = self.tokens.get(self.next_token).map(position);
+        self.flush_comments(position);
+
+        self.indent -= 1;
+        self.end_line();
+        self.token("}");
+        self.end_line();
+    }
+
+    fn expression(&mut self, expr: &Expr)
+    {
+        match expr
+        {
+            Expr::Assign { name, value, .. } =>
+            {
+                self.token(&name.lexeme);
+                self.space();
+                self.token("=");
+                self.space();
+                self.expression(value);
+            }
+            Expr::Binary { left, operator, right } | Expr::Logical { left, operator, right } =>
+            {
+                self.expression(left);
+                self.space();
+                self.token(&operator.lexeme);
+                self.space();
+                self.expression(right);
+            }
+            Expr::Call { callee, arguments, .. } =>
+            {
+                self.expression(callee);
+                self.token("(");
+
+                for (i, argument) in arguments.iter().enumerate()
+                {
+                    if i > 0
+                    {
+                        self.token(",");
+                        self.space();
+                    }
+
+                    self.expression(argument);
+                }
+
+                self.token(")");
+            }
+            Expr::Get { object, name } =>
+            {
+                self.expression(object);
+                self.token(".");
+                self.token(&name.lexeme);
+            }
+            Expr::Grouping { expression } =>
+            {
+                self.token("(");
+                self.expression(expression);
+                self.token(")");
+            }
+            Expr::Literal { value } => self.token(&literal(value)),
+            Expr::Set { object, name, value } =>
+            {
+                self.expression(object);
+                self.token(".");
+                self.token(&name.lexeme);
+                self.space();
+                self.token("=");
+                self.space();
+                self.expression(value);
+            }
+            Expr::Super { keyword, method, .. } =>
+            {
+                self.token(&keyword.lexeme);
+                self.token(".");
+                self.token(&method.lexeme);
+            }
+            Expr::This { keyword, .. } => self.token(&keyword.lexeme),
+            Expr::Unary { operator, right } =>
+            {
+                self.token(&operator.lexeme);
+                self.expression(right);
+            }
+            Expr::Variable { name, .. } => self.token(&name.lexeme),
+        }
+    }
+
+    // Writes the next source token, after any comments that came before it
+    fn token(&mut self, text: &str)
+    {
+        let source = self.tokens.get(self.next_token);
+        self.flush_comments(source.map(position));
+
+        let line = source.map_or(self.last_line, |token| token.line);
+        self.write(text, line);
+
+        if let Some(token) = source
+        {
+            self.last_line = last_line_of(token);
+        }
+        self.next_token += 1;
+    }
+
+    fn flush_comments(&mut self, before: Option<(i32, i32)>)
+    {
+        while let Some(comment) = self.comments.get(self.next_comment)
+        {
+            if before.is_some_and(|before| position(comment) >= before)
+            {
+                break;
+            }
+
+            self.comment(comment);
+            self.next_comment += 1;
+        }
+    }
+
+    fn comment(&mut self, comment: &Token)
+    {
+        // Comments after code on the same line stay there
+        let trailing = !self.line.is_empty()
+            && self.next_token > 0
+            && self.tokens.get(self.next_token - 1).is_some_and(|token| last_line_of(token) == comment.line);
+
+        if trailing
+        {
+            self.line.push(' ');
+            self.line.push_str(&comment.lexeme);
+        }
+        else
+        {
+            // Whatever the comment sits in front of still starts its own line
+            let statement_start = self.statement_start;
+
+            self.end_line();
+            self.statement_start = true;
+            self.write(&comment.lexeme, comment.line);
+            self.statement_start = statement_start;
+        }
+
+        self.line_done = true;
+        self.last_line = comment.line;
+    }
+
+    fn write(&mut self, text: &str, source_line: i32)
+    {
+        if self.line_done
+        {
+            self.commit();
+        }
+
+        if self.line.is_empty()
+        {
+            let blank = self.statement_start
+                && !self.after_open_brace
+                && !self.lines.is_empty()
+                && source_line > self.last_line + 1;
+
+            if blank
+            {
+                self.lines.push(String::new());
+            }
+
+            self.line = INDENT.repeat(self.indent);
+        }
+
+        self.statement_start = false;
+        self.after_open_brace = false;
+        self.line.push_str(text);
+    }
+
+    fn space(&mut self)
+    {
+        self.line.push(' ');
+    }
+
+    // The break only happens once something else is written
+    fn end_line(&mut self)
+    {
+        if !self.line.is_empty()
+        {
+            self.line_done = true;
+        }
+    }
+
+    fn commit(&mut self)
+    {
+        let line = std::mem::take(&mut self.line);
+        self.lines.push(line);
+        self.line_done = false;
+    }
+}
+
+fn position(token: &Token) -> (i32, i32)
+{
+    (token.line, token.column)
+}
+
+// Strings can run over several lines
+fn last_line_of(token: &Token) -> i32
+{
+    token.line + token.lexeme.matches('\n').count() as i32
+}
+
+fn literal(value: &Literal) -> String
+{
+    match value
+    {
+        Literal::Null => "null".to_string(),
+        Literal::Bool(value) => value.to_string(),
+        Literal::Number(value) => value.to_string(),
+        Literal::String(value) => escape(value),
+    }
+}
+
+// Quotes the string again, so anything unprintable comes back as an escape
+fn escape(value: &str) -> String
+{
+    let mut text = String::from("\"");
+
+    for c in value.chars()
+    {
+        match c
+        {
+            '\n' => text.push_str("\\n"),
+            '\t' => text.push_str("\\t"),
+            '\r' => text.push_str("\\r"),
+            '\0' => text.push_str("\\0"),
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            c if c.is_control() => text.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => text.push(c),
+        }
+    }
+
+    text.push('"');
+    text
+}
@@ -0,0 +1,598 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    environment::Environment,
+    error::{ErrorHandler, RuntimeError},
+    expr::{Depth, Expr, Literal},
+    stmt::Stmt,
+    tokens::{Token, TokenType},
+    value::{LoxClass, LoxFunction, LoxInstance, NativeFn, NativeFunction, Value},
+};
+
+// Same call depth limit as the bytecode VM, where the script takes up a frame too
+const FRAMES_MAX: usize = 64;
+
+// Anything that stops statements running in order
+enum Unwind
+{
+    Error(RuntimeError),
+    Return(Value),
+}
+
+impl From<RuntimeError> for Unwind
+{
+    fn from(error: RuntimeError) -> Self
+    {
+        Unwind::Error(error)
+    }
+}
+
+type ExecResult = Result<(), Unwind>;
+type EvalResult = Result<Value, RuntimeError>;
+
+pub struct Interpreter
+{
+    globals: Rc<RefCell<Environment>>,
+    environment: Rc<RefCell<Environment>>,
+    // Function calls currently running
+    depth: usize,
+    // Where print statements go, stdout unless replaced
+    output: Box<dyn Write>,
+    // Command line arguments, the script's own path first
+    args: Vec<String>,
+}
+
+impl Default for Interpreter
+{
+    fn default() -> Self
+    {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter
+{
+    pub fn new() -> Interpreter
+    {
+        let globals = Rc::new(RefCell::new(Environment::new()));
+
+        let mut interpreter = Interpreter
+        {
+            environment: globals.clone(),
+            globals,
+            depth: 0,
+            output: Box::new(io::stdout()),
+            args: Vec::new(),
+        };
+
+        interpreter.define_native("clock", 0, clock);
+        interpreter.define_native("argc", 0, argc);
+        interpreter.define_native("argv", 1, argv);
+        interpreter
+    }
+
+    // Exposes a Rust function to scripts as a global
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn)
+    {
+        let native = NativeFunction { name: name.to_string(), arity, function };
+        self.globals.borrow_mut().define(name, Value::Native(Rc::new(native)));
+    }
+
+    // Lets the output of a script be captured, ie. by tests
+    pub fn set_output(&mut self, output: Box<dyn Write>)
+    {
+        self.output = output;
+    }
+
+    // What argc and argv give the script
+    pub fn set_args(&mut self, args: Vec<String>)
+    {
+        self.args = args;
+    }
+
+    // Stops at the first runtime error, reporting it through the handler.
+    // Globals defined so far are kept, so a REPL can carry on afterwards.
+    pub fn interpret(&mut self, statements: &[Stmt], errors: &mut ErrorHandler)
+    {
+        for statement in statements
+        {
+            match self.execute(statement)
+            {
+                Ok(()) => {},
+                Err(Unwind::Error(error)) =>
+                {
+                    errors.runtime_error(error);
+                    self.environment = self.globals.clone();
+                    self.depth = 0;
+                    return;
+                }
+                // Only reachable from top-level code, which just ends the script
+                Err(Unwind::Return(_)) => return,
+            }
+        }
+    }
+
+    fn execute(&mut self, statement: &Stmt) -> ExecResult
+    {
+        match statement
+        {
+            Stmt::Block { statements } =>
+            {
+                let environment = Environment::with_enclosing(self.environment.clone());
+                self.execute_block(statements, environment)
+            }
+            Stmt::Class { name, superclass, methods } =>
+            {
+                let superclass = match superclass
+                {
+                    Some(expr) => match self.evaluate(expr)?
+                    {
+                        Value::Class(class) => Some(class),
+                        _ =>
+                        {
+                            let token = match expr { Expr::Variable { name, .. } => name, _ => name };
+                            return Err(RuntimeError::new(token, "Superclass must be a class.".to_string()).into());
+                        }
+                    },
+                    None => None,
+                };
+
+                self.environment.borrow_mut().define(&name.lexeme, Value::Null);
+
+                // Methods of a subclass close over a scope holding 'parent'
+                let closure = match &superclass
+                {
+                    Some(superclass) =>
+                    {
+                        let mut environment = Environment::with_enclosing(self.environment.clone());
+                        environment.define("parent", Value::Class(superclass.clone()));
+                        Rc::new(RefCell::new(environment))
+                    }
+                    None => self.environment.clone(),
+                };
+
+                let methods: HashMap<String, Rc<LoxFunction>> = methods.iter()
+                    .map(|method|
+                    {
+                        let function = LoxFunction
+                        {
+                            declaration: method.clone(),
+                            closure: closure.clone(),
+                            is_initializer: method.name.lexeme == "init",
+                        };
+
+                        (method.name.lexeme.clone(), Rc::new(function))
+                    })
+                    .collect();
+
+                let class = LoxClass { name: name.lexeme.clone(), superclass, methods };
+                self.environment.borrow_mut().assign(name, Value::Class(Rc::new(class)))?;
+                Ok(())
+            }
+            Stmt::Expression { expression } =>
+            {
+                self.evaluate(expression)?;
+                Ok(())
+            }
+            Stmt::Function { function } =>
+            {
+                let value = LoxFunction
+                {
+                    declaration: function.clone(),
+                    closure: self.environment.clone(),
+                    is_initializer: false,
+                };
+
+                self.environment.borrow_mut().define(&function.name.lexeme, Value::Function(Rc::new(value)));
+                Ok(())
+            }
+            Stmt::If { condition, then_branch, else_branch } =>
+            {
+                if !self.evaluate(condition)?.is_falsey()
+                {
+                    self.execute(then_branch)
+                }
+                else if let Some(else_branch) = else_branch
+                {
+                    self.execute(else_branch)
+                }
+                else
+                {
+                    Ok(())
+                }
+            }
+            Stmt::Print { expression } =>
+            {
+                let value = self.evaluate(expression)?;
+                writeln!(self.output, "{}", value).expect("Failed to write output.");
+                Ok(())
+            }
+            Stmt::Return { value, .. } =>
+            {
+                let value = match value
+                {
+                    Some(value) => self.evaluate(value)?,
+                    None => Value::Null,
+                };
+
+                Err(Unwind::Return(value))
+            }
+            Stmt::Var { name, initializer } =>
+            {
+                let value = match initializer
+                {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Null,
+                };
+
+                self.environment.borrow_mut().define(&name.lexeme, value);
+                Ok(())
+            }
+            Stmt::While { condition, body } =>
+            {
+                while !self.evaluate(condition)?.is_falsey()
+                {
+                    self.execute(body)?;
+                }
+
+                Ok(())
+            }
+            Stmt::For { initializer, condition, increment, body } =>
+            {
+                // The loop variable lives in its own scope around the loop
+                let environment = Environment::with_enclosing(self.environment.clone());
+                let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+
+                let result = self.execute_for(initializer.as_deref(), condition.as_ref(), increment.as_ref(), body);
+
+                self.environment = previous;
+                result
+            }
+        }
+    }
+
+    fn execute_for(&mut self, initializer: Option<&Stmt>, condition: Option<&Expr>, increment: Option<&Expr>, body: &Stmt) -> ExecResult
+    {
+        if let Some(initializer) = initializer
+        {
+            self.execute(initializer)?;
+        }
+
+        loop
+        {
+            if let Some(condition) = condition
+            {
+                if self.evaluate(condition)?.is_falsey() { break; }
+            }
+
+            self.execute(body)?;
+
+            if let Some(increment) = increment
+            {
+                self.evaluate(increment)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs the statements in the given scope, restoring the current one however they finish
+    fn execute_block(&mut self, statements: &[Stmt], environment: Environment) -> ExecResult
+    {
+        let previous = std::mem::replace(&mut self.environment, Rc::new(RefCell::new(environment)));
+
+        let result = statements.iter().try_for_each(|statement| self.execute(statement));
+
+        self.environment = previous;
+        result
+    }
+
+    fn evaluate(&mut self, expr: &Expr) -> EvalResult
+    {
+        match expr
+        {
+            Expr::Assign { name, value, depth } =>
+            {
+                let value = self.evaluate(value)?;
+
+                match depth.get()
+                {
+                    Some(depth) => self.environment.borrow_mut().assign_at(depth, name, value.clone())?,
+                    None => self.globals.borrow_mut().assign(name, value.clone())?,
+                }
+
+                Ok(value)
+            }
+            Expr::Binary { left, operator, right } =>
+            {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(operator, left, right)
+            }
+            Expr::Call { callee, paren, arguments } =>
+            {
+                // A method call on something that isn't an instance gets its own error, as in the VM
+                let callee = match callee.as_ref()
+                {
+                    Expr::Get { object, name } => match self.evaluate(object)?
+                    {
+                        Value::Instance(instance) => get_property(&instance, name)?,
+                        _ => return Err(RuntimeError::new(name, "Only instances have methods.".to_string())),
+                    },
+                    _ => self.evaluate(callee)?,
+                };
+
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments
+                {
+                    values.push(self.evaluate(argument)?);
+                }
+
+                self.call_value(callee, values, paren)
+            }
+            Expr::Get { object, name } =>
+            {
+                match self.evaluate(object)?
+                {
+                    Value::Instance(instance) => get_property(&instance, name),
+                    _ => Err(RuntimeError::new(name, "Only instances have properties.".to_string())),
+                }
+            }
+            Expr::Grouping { expression } => self.evaluate(expression),
+            Expr::Literal { value } => Ok(match value
+            {
+                Literal::Null => Value::Null,
+                Literal::Bool(value) => Value::Bool(*value),
+                Literal::Number(value) => Value::Number(*value),
+                Literal::String(value) => Value::String(Rc::from(value.as_str())),
+            }),
+            Expr::Logical { left, operator, right } =>
+            {
+                let left = self.evaluate(left)?;
+
+                // Short circuit, returning the deciding operand itself
+                let done = match operator.type_of
+                {
+                    TokenType::Or => !left.is_falsey(),
+                    _ => left.is_falsey(),
+                };
+
+                if done { Ok(left) } else { self.evaluate(right) }
+            }
+            Expr::Set { object, name, value } =>
+            {
+                let object = self.evaluate(object)?;
+                let value = self.evaluate(value)?;
+
+                match object
+                {
+                    Value::Instance(instance) =>
+                    {
+                        instance.borrow_mut().fields.insert(name.lexeme.clone(), value.clone());
+                        Ok(value)
+                    }
+                    _ => Err(RuntimeError::new(name, "Only instances have fields.".to_string())),
+                }
+            }
+            Expr::Super { keyword, method, depth } =>
+            {
+                let depth = depth.get().unwrap_or(0);
+
+                let superclass = match self.environment.borrow().get_at(depth, "parent")
+                {
+                    Some(Value::Class(class)) => class,
+                    _ => return Err(RuntimeError::new(keyword, "Can't use 'parent' outside of a class.".to_string())),
+                };
+
+                // 'this' is bound in the scope just inside the one holding 'parent'
+                let instance = match self.environment.borrow().get_at(depth.saturating_sub(1), "this")
+                {
+                    Some(Value::Instance(instance)) => instance,
+                    _ => return Err(RuntimeError::new(keyword, "Can't use 'parent' outside of a class.".to_string())),
+                };
+
+                match superclass.find_method(&method.lexeme)
+                {
+                    Some(found) => Ok(Value::Function(Rc::new(found.bind(instance)))),
+                    None => Err(RuntimeError::new(method, format!("Undefined property '{}'.", method.lexeme))),
+                }
+            }
+            Expr::This { keyword, depth } =>
+            {
+                match self.environment.borrow().get_at(depth.get().unwrap_or(0), "this")
+                {
+                    Some(value) => Ok(value),
+                    None => Err(RuntimeError::new(keyword, "Can't use 'this' outside of a class.".to_string())),
+                }
+            }
+            Expr::Unary { operator, right } =>
+            {
+                let right = self.evaluate(right)?;
+
+                match (operator.type_of, right)
+                {
+                    (TokenType::Bang, right) => Ok(Value::Bool(right.is_falsey())),
+                    (_, Value::Number(value)) => Ok(Value::Number(-value)),
+                    _ => Err(RuntimeError::new(operator, "Operand must be a number.".to_string())),
+                }
+            }
+            Expr::Variable { name, depth } => self.look_up_variable(name, depth),
+        }
+    }
+
+    // Locals are found where the resolver said they would be, anything else is a global
+    fn look_up_variable(&self, name: &Token, depth: &Depth) -> EvalResult
+    {
+        let value = match depth.get()
+        {
+            Some(depth) => self.environment.borrow().get_at(depth, &name.lexeme),
+            None => self.globals.borrow().lookup(&name.lexeme),
+        };
+
+        value.ok_or_else(|| RuntimeError::new(name, format!("Undefined variable '{}'.", name.lexeme)))
+    }
+
+    fn call_value(&mut self, callee: Value, arguments: Vec<Value>, paren: &Token) -> EvalResult
+    {
+        match callee
+        {
+            Value::Function(function) =>
+            {
+                check_arity(function.arity(), arguments.len(), paren)?;
+                self.call(&function, arguments, paren)
+            }
+            Value::Native(native) =>
+            {
+                check_arity(native.arity, arguments.len(), paren)?;
+                (native.function)(self, &arguments).map_err(|message| RuntimeError::new(paren, message))
+            }
+            Value::Class(class) =>
+            {
+                let instance = Rc::new(RefCell::new(LoxInstance { class: class.clone(), fields: HashMap::new() }));
+
+                match class.find_method("init")
+                {
+                    Some(init) =>
+                    {
+                        check_arity(init.arity(), arguments.len(), paren)?;
+                        self.call(&init.bind(instance.clone()), arguments, paren)?;
+                    }
+                    None => check_arity(0, arguments.len(), paren)?,
+                }
+
+                Ok(Value::Instance(instance))
+            }
+            _ => Err(RuntimeError::new(paren, "Can only call functions and classes.".to_string())),
+        }
+    }
+
+    fn call(&mut self, function: &LoxFunction, arguments: Vec<Value>, paren: &Token) -> EvalResult
+    {
+        if self.depth + 1 >= FRAMES_MAX
+        {
+            return Err(RuntimeError::new(paren, "Stack overflow.".to_string()));
+        }
+
+        let mut environment = Environment::with_enclosing(function.closure.clone());
+        for (param, argument) in function.declaration.params.iter().zip(arguments)
+        {
+            environment.define(&param.lexeme, argument);
+        }
+
+        self.depth += 1;
+        let result = self.execute_block(&function.declaration.body, environment);
+        self.depth -= 1;
+
+        let value = match result
+        {
+            Ok(()) => Value::Null,
+            Err(Unwind::Return(value)) => value,
+            Err(Unwind::Error(error)) => return Err(error),
+        };
+
+        // Initializers always hand back the instance
+        if function.is_initializer
+        {
+            return Ok(function.closure.borrow().get_at(0, "this").unwrap_or(Value::Null));
+        }
+
+        Ok(value)
+    }
+}
+
+fn check_arity(arity: usize, count: usize, paren: &Token) -> Result<(), RuntimeError>
+{
+    if arity != count
+    {
+        return Err(RuntimeError::new(paren, format!("Expected {} arguments but got {}.", arity, count)));
+    }
+
+    Ok(())
+}
+
+// Fields shadow methods, methods come back bound to the instance
+fn get_property(instance: &Rc<RefCell<LoxInstance>>, name: &Token) -> EvalResult
+{
+    if let Some(value) = instance.borrow().fields.get(&name.lexeme)
+    {
+        return Ok(value.clone());
+    }
+
+    let method = instance.borrow().class.find_method(&name.lexeme);
+    match method
+    {
+        Some(method) => Ok(Value::Function(Rc::new(method.bind(instance.clone())))),
+        None => Err(RuntimeError::new(name, format!("Undefined property '{}'.", name.lexeme))),
+    }
+}
+
+fn binary(operator: &Token, left: Value, right: Value) -> EvalResult
+{
+    use TokenType::*;
+
+    match operator.type_of
+    {
+        EqualEqual => return Ok(Value::Bool(left == right)),
+        BangEqual => return Ok(Value::Bool(left != right)),
+        Plus => return match (left, right)
+        {
+            (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::String(Rc::from(format!("{}{}", a, b)))),
+            _ => Err(RuntimeError::new(operator, "Operands must be two numbers or two strings.".to_string())),
+        },
+        _ => {}
+    }
+
+    let (a, b) = match (left, right)
+    {
+        (Value::Number(a), Value::Number(b)) => (a, b),
+        _ => return Err(RuntimeError::new(operator, "Operands must be numbers.".to_string())),
+    };
+
+    Ok(match operator.type_of
+    {
+        Minus => Value::Number(a - b),
+        Star => Value::Number(a * b),
+        Slash => Value::Number(a / b),
+        Greater => Value::Bool(a > b),
+        Less => Value::Bool(a < b),
+        GreaterEqual => Value::Bool(a >= b),
+        LessEqual => Value::Bool(a <= b),
+        _ => unreachable!(),
+    })
+}
+
+fn clock(_interpreter: &mut Interpreter, _args: &[Value]) -> Result<Value, String>
+{
+    match SystemTime::now().duration_since(UNIX_EPOCH)
+    {
+        Ok(time) => Ok(Value::Number(time.as_secs_f64())),
+        Err(_) => Err("System clock is before the epoch.".to_string()),
+    }
+}
+
+fn argc(interpreter: &mut Interpreter, _args: &[Value]) -> Result<Value, String>
+{
+    Ok(Value::Number(interpreter.args.len() as f64))
+}
+
+fn argv(interpreter: &mut Interpreter, args: &[Value]) -> Result<Value, String>
+{
+    let index = match args[0]
+    {
+        Value::Number(index) => index,
+        _ => return Err("Argument index must be a number.".to_string()),
+    };
+
+    match interpreter.args.get(index as usize)
+    {
+        Some(arg) if index.fract() == 0.0 && index >= 0.0 => Ok(Value::String(arg.as_str().into())),
+        _ => Err("Argument index out of range.".to_string()),
+    }
+}
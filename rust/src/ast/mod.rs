@@ -1,4 +1,11 @@
 pub mod tokens;
 pub mod scanner;
 pub mod error;
-pub mod lang;
\ No newline at end of file
+pub mod expr;
+pub mod stmt;
+pub mod parser;
+pub mod resolver;
+pub mod value;
+pub mod environment;
+pub mod interpreter;
+pub mod formatter;
@@ -0,0 +1,558 @@
+use std::rc::Rc;
+
+use super::{
+    error::ErrorHandler,
+    expr::{Depth, Expr, Literal},
+    stmt::{Function, Stmt},
+    tokens::{Token, TokenType},
+};
+
+const ARGS_MAX: usize = 255;
+
+// Raised once the error has been reported, it only unwinds to the next statement
+struct ParseError;
+
+type ParseResult<T> = Result<T, ParseError>;
+
+pub struct Parser<'a>
+{
+    tokens: Vec<Token>,
+    current: usize,
+    errors: &'a mut ErrorHandler,
+}
+
+impl<'a> Parser<'a>
+{
+    // The tokens must end with an EOF, as produced by the scanner
+    pub fn new(tokens: Vec<Token>, errors: &'a mut ErrorHandler) -> Parser<'a>
+    {
+        Parser
+        {
+            tokens,
+            current: 0,
+            errors,
+        }
+    }
+
+    // Statements that failed to parse are left out, check the ErrorHandler before running
+    pub fn parse(&mut self) -> Vec<Stmt>
+    {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end()
+        {
+            if let Some(statement) = self.declaration()
+            {
+                statements.push(statement);
+            }
+        }
+
+        statements
+    }
+
+    fn declaration(&mut self) -> Option<Stmt>
+    {
+        use TokenType::*;
+
+        let result = if self.match_types(&[Class])
+        {
+            self.class_declaration()
+        }
+        else if self.match_types(&[Func])
+        {
+            self.function("function").map(|function| Stmt::Function { function })
+        }
+        else if self.match_types(&[Var])
+        {
+            self.var_declaration()
+        }
+        else
+        {
+            self.statement()
+        };
+
+        match result
+        {
+            Ok(statement) => Some(statement),
+            Err(_) =>
+            {
+                self.synchronize();
+                None
+            }
+        }
+    }
+
+    fn class_declaration(&mut self) -> ParseResult<Stmt>
+    {
+        let name = self.consume(TokenType::Identifier, "Expect class name.")?;
+
+        let superclass = match self.match_types(&[TokenType::Less])
+        {
+            true =>
+            {
+                let name = self.consume(TokenType::Identifier, "Expect superclass name.")?;
+                Some(Expr::Variable { name, depth: Depth::default() })
+            }
+            false => None,
+        };
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.")?;
+
+        // Methods are declared without 'fn'
+        let mut methods = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end()
+        {
+            methods.push(self.function("method")?);
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.")?;
+
+        Ok(Stmt::Class { name, superclass, methods })
+    }
+
+    fn function(&mut self, kind: &str) -> ParseResult<Rc<Function>>
+    {
+        let name = self.consume(TokenType::Identifier, &format!("Expect {} name.", kind))?;
+        self.consume(TokenType::LeftParen, &format!("Expect '(' after {} name.", kind))?;
+
+        let mut params = Vec::new();
+        if !self.check(TokenType::RightParen)
+        {
+            loop
+            {
+                if params.len() >= ARGS_MAX
+                {
+                    // Reported, but the parser is still in a sane state so carry on
+                    let token = self.peek().clone();
+                    self.error(&token, "Can't have more than 255 parameters.");
+                }
+
+                params.push(self.consume(TokenType::Identifier, "Expect parameter name.")?);
+
+                if !self.match_types(&[TokenType::Comma]) { break; }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.")?;
+        self.consume(TokenType::LeftBrace, &format!("Expect '{{' before {} body.", kind))?;
+
+        let body = self.block()?;
+
+        Ok(Rc::new(Function { name, params, body }))
+    }
+
+    fn var_declaration(&mut self) -> ParseResult<Stmt>
+    {
+        let name = self.consume(TokenType::Identifier, "Expect variable name.")?;
+
+        let initializer = match self.match_types(&[TokenType::Equal])
+        {
+            true => Some(self.expression()?),
+            false => None,
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.")?;
+        Ok(Stmt::Var { name, initializer })
+    }
+
+    fn statement(&mut self) -> ParseResult<Stmt>
+    {
+        use TokenType::*;
+
+        if self.match_types(&[For]) { return self.for_statement(); }
+        if self.match_types(&[If]) { return self.if_statement(); }
+        if self.match_types(&[Print]) { return self.print_statement(); }
+        if self.match_types(&[Return]) { return self.return_statement(); }
+        if self.match_types(&[While]) { return self.while_statement(); }
+        if self.match_types(&[LeftBrace]) { return Ok(Stmt::Block { statements: self.block()? }); }
+
+        self.expression_statement()
+    }
+
+    fn for_statement(&mut self) -> ParseResult<Stmt>
+    {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.match_types(&[TokenType::Semicolon])
+        {
+            None
+        }
+        else if self.match_types(&[TokenType::Var])
+        {
+            Some(Box::new(self.var_declaration()?))
+        }
+        else
+        {
+            Some(Box::new(self.expression_statement()?))
+        };
+
+        let condition = match self.check(TokenType::Semicolon)
+        {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TokenType::Semicolon, "Expect ';' after loop condition.")?;
+
+        let increment = match self.check(TokenType::RightParen)
+        {
+            true => None,
+            false => Some(self.expression()?),
+        };
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.")?;
+
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::For { initializer, condition, increment, body })
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Stmt>
+    {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after if condition.")?;
+
+        let then_branch = Box::new(self.statement()?);
+
+        // An else belongs to the nearest if
+        let else_branch = match self.match_types(&[TokenType::Else])
+        {
+            true => Some(Box::new(self.statement()?)),
+            false => None,
+        };
+
+        Ok(Stmt::If { condition, then_branch, else_branch })
+    }
+
+    fn print_statement(&mut self) -> ParseResult<Stmt>
+    {
+        let expression = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after value.")?;
+        Ok(Stmt::Print { expression })
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Stmt>
+    {
+        let keyword = self.previous().clone();
+
+        let value = match self.check(TokenType::Semicolon)
+        {
+            true => None,
+            false => Some(self.expression()?),
+        };
+
+        self.consume(TokenType::Semicolon, "Expect ';' after return value.")?;
+        Ok(Stmt::Return { keyword, value })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Stmt>
+    {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "Expect ')' after condition.")?;
+
+        let body = Box::new(self.statement()?);
+
+        Ok(Stmt::While { condition, body })
+    }
+
+    // Called just after the '{'
+    fn block(&mut self) -> ParseResult<Vec<Stmt>>
+    {
+        let mut statements = Vec::new();
+
+        while !self.check(TokenType::RightBrace) && !self.is_at_end()
+        {
+            if let Some(statement) = self.declaration()
+            {
+                statements.push(statement);
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.")?;
+        Ok(statements)
+    }
+
+    fn expression_statement(&mut self) -> ParseResult<Stmt>
+    {
+        let expression = self.expression()?;
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.")?;
+        Ok(Stmt::Expression { expression })
+    }
+
+    fn expression(&mut self) -> ParseResult<Expr>
+    {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ParseResult<Expr>
+    {
+        let expr = self.or()?;
+
+        if self.match_types(&[TokenType::Equal])
+        {
+            let equals = self.previous().clone();
+            let value = Box::new(self.assignment()?);
+
+            // The left side was parsed as an expression, so turn it into a target
+            return match expr
+            {
+                Expr::Variable { name, .. } => Ok(Expr::Assign { name, value, depth: Depth::default() }),
+                Expr::Get { object, name } => Ok(Expr::Set { object, name, value }),
+                _ =>
+                {
+                    // Nothing to recover from, report and keep parsing
+                    self.error(&equals, "Invalid assignment target.");
+                    Ok(expr)
+                }
+            };
+        }
+
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> ParseResult<Expr>
+    {
+        let mut expr = self.and()?;
+
+        while self.match_types(&[TokenType::Or])
+        {
+            let operator = self.previous().clone();
+            let right = Box::new(self.and()?);
+            expr = Expr::Logical { left: Box::new(expr), operator, right };
+        }
+
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> ParseResult<Expr>
+    {
+        let mut expr = self.equality()?;
+
+        while self.match_types(&[TokenType::And])
+        {
+            let operator = self.previous().clone();
+            let right = Box::new(self.equality()?);
+            expr = Expr::Logical { left: Box::new(expr), operator, right };
+        }
+
+        Ok(expr)
+    }
+
+    // Left associative binary operators one precedence level above next
+    fn binary(&mut self, types: &[TokenType], next: fn(&mut Self) -> ParseResult<Expr>) -> ParseResult<Expr>
+    {
+        let mut expr = next(self)?;
+
+        while self.match_types(types)
+        {
+            let operator = self.previous().clone();
+            let right = Box::new(next(self)?);
+            expr = Expr::Binary { left: Box::new(expr), operator, right };
+        }
+
+        Ok(expr)
+    }
+
+    fn equality(&mut self) -> ParseResult<Expr>
+    {
+        self.binary(&[TokenType::BangEqual, TokenType::EqualEqual], Self::comparison)
+    }
+
+    fn comparison(&mut self) -> ParseResult<Expr>
+    {
+        use TokenType::*;
+        self.binary(&[Greater, GreaterEqual, Less, LessEqual], Self::term)
+    }
+
+    fn term(&mut self) -> ParseResult<Expr>
+    {
+        self.binary(&[TokenType::Minus, TokenType::Plus], Self::factor)
+    }
+
+    fn factor(&mut self) -> ParseResult<Expr>
+    {
+        self.binary(&[TokenType::Slash, TokenType::Star], Self::unary)
+    }
+
+    fn unary(&mut self) -> ParseResult<Expr>
+    {
+        if self.match_types(&[TokenType::Bang, TokenType::Minus])
+        {
+            let operator = self.previous().clone();
+            let right = Box::new(self.unary()?);
+            return Ok(Expr::Unary { operator, right });
+        }
+
+        self.call()
+    }
+
+    fn call(&mut self) -> ParseResult<Expr>
+    {
+        let mut expr = self.primary()?;
+
+        loop
+        {
+            if self.match_types(&[TokenType::LeftParen])
+            {
+                expr = self.finish_call(expr)?;
+            }
+            else if self.match_types(&[TokenType::Dot])
+            {
+                let name = self.consume(TokenType::Identifier, "Expect property name after '.'.")?;
+                expr = Expr::Get { object: Box::new(expr), name };
+            }
+            else
+            {
+                break;
+            }
+        }
+
+        Ok(expr)
+    }
+
+    fn finish_call(&mut self, callee: Expr) -> ParseResult<Expr>
+    {
+        let mut arguments = Vec::new();
+
+        if !self.check(TokenType::RightParen)
+        {
+            loop
+            {
+                if arguments.len() >= ARGS_MAX
+                {
+                    let token = self.peek().clone();
+                    self.error(&token, "Can't have more than 255 arguments.");
+                }
+
+                arguments.push(self.expression()?);
+
+                if !self.match_types(&[TokenType::Comma]) { break; }
+            }
+        }
+
+        let paren = self.consume(TokenType::RightParen, "Expect ')' after arguments.")?;
+
+        Ok(Expr::Call { callee: Box::new(callee), paren, arguments })
+    }
+
+    fn primary(&mut self) -> ParseResult<Expr>
+    {
+        use TokenType::*;
+
+        let token = self.peek().clone();
+
+        let expr = match token.type_of
+        {
+            False => Expr::Literal { value: Literal::Bool(false) },
+            True => Expr::Literal { value: Literal::Bool(true) },
+            Null => Expr::Literal { value: Literal::Null },
+            Number =>
+            {
+                // The scanner only hands out well formed numbers
+                let value = token.literal.parse().unwrap_or(0.0);
+                Expr::Literal { value: Literal::Number(value) }
+            }
+            String => Expr::Literal { value: Literal::String(token.literal.clone()) },
+            This => Expr::This { keyword: token, depth: Depth::default() },
+            Identifier => Expr::Variable { name: token, depth: Depth::default() },
+            Super =>
+            {
+                self.advance();
+                self.consume(Dot, "Expect '.' after 'parent'.")?;
+                let method = self.consume(Identifier, "Expect superclass method name.")?;
+                return Ok(Expr::Super { keyword: token, method, depth: Depth::default() });
+            }
+            LeftParen =>
+            {
+                self.advance();
+                let expression = Box::new(self.expression()?);
+                self.consume(RightParen, "Expect ')' after expression.")?;
+                return Ok(Expr::Grouping { expression });
+            }
+            _ => return Err(self.error(&token, "Expect expression.")),
+        };
+
+        self.advance();
+        Ok(expr)
+    }
+
+    fn match_types(&mut self, types: &[TokenType]) -> bool
+    {
+        if types.iter().any(|type_of| self.check(*type_of))
+        {
+            self.advance();
+            return true;
+        }
+
+        false
+    }
+
+    fn consume(&mut self, type_of: TokenType, message: &str) -> ParseResult<Token>
+    {
+        if self.check(type_of)
+        {
+            return Ok(self.advance().clone());
+        }
+
+        let token = self.peek().clone();
+        Err(self.error(&token, message))
+    }
+
+    fn check(&self, type_of: TokenType) -> bool
+    {
+        !self.is_at_end() && self.peek().type_of == type_of
+    }
+
+    fn advance(&mut self) -> &Token
+    {
+        if !self.is_at_end() { self.current += 1; }
+        self.previous()
+    }
+
+    fn is_at_end(&self) -> bool
+    {
+        self.peek().type_of == TokenType::EOF
+    }
+
+    fn peek(&self) -> &Token
+    {
+        &self.tokens[self.current]
+    }
+
+    fn previous(&self) -> &Token
+    {
+        &self.tokens[self.current - 1]
+    }
+
+    fn error(&mut self, token: &Token, message: &str) -> ParseError
+    {
+        let found_at = match token.type_of
+        {
+            TokenType::EOF => "at end".to_string(),
+            _ => format!("at '{}'", token.lexeme),
+        };
+
+        self.errors.report(token.line, found_at, message.to_string());
+        ParseError
+    }
+
+    // Skip tokens until we reach something that looks like a statement boundary
+    fn synchronize(&mut self)
+    {
+        self.advance();
+
+        use TokenType::*;
+        while !self.is_at_end()
+        {
+            if self.previous().type_of == Semicolon { return; }
+
+            match self.peek().type_of
+            {
+                Class | Func | Var | For | If | While | Print | Return => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+}
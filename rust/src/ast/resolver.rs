@@ -0,0 +1,379 @@
+use std::{
+    collections::HashMap,
+    rc::Rc,
+};
+
+use super::{
+    error::ErrorHandler,
+    expr::{Depth, Expr},
+    stmt::{Function, Stmt},
+    tokens::{Token, TokenType},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum FunctionType
+{
+    None,
+    Function,
+    Initializer,
+    Method,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ClassType
+{
+    None,
+    Class,
+    Subclass,
+}
+
+struct Local
+{
+    token: Token,
+    // False between declaring and defining, while the initializer runs
+    defined: bool,
+    used: bool,
+}
+
+// Walks the tree once before it runs, working out which scope every local
+// is in so closures see the variables they were written next to. Scopes here
+// have to line up one to one with the environments the interpreter makes.
+pub struct Resolver<'a>
+{
+    // Innermost last, globals are not tracked
+    scopes: Vec<HashMap<String, Local>>,
+    current_function: FunctionType,
+    current_class: ClassType,
+    errors: &'a mut ErrorHandler,
+}
+
+impl<'a> Resolver<'a>
+{
+    pub fn new(errors: &'a mut ErrorHandler) -> Resolver<'a>
+    {
+        Resolver
+        {
+            scopes: Vec::new(),
+            current_function: FunctionType::None,
+            current_class: ClassType::None,
+            errors,
+        }
+    }
+
+    // Errors and warnings go to the ErrorHandler, check it before running
+    pub fn resolve(&mut self, statements: &[Stmt])
+    {
+        for statement in statements
+        {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt)
+    {
+        match statement
+        {
+            Stmt::Block { statements } =>
+            {
+                self.begin_scope();
+                self.resolve(statements);
+                self.end_scope();
+            }
+            Stmt::Class { name, superclass, methods } => self.class(name, superclass.as_ref(), methods),
+            Stmt::Expression { expression } => self.expression(expression),
+            Stmt::Function { function } =>
+            {
+                // Defined straight away so the function can call itself
+                self.declare(function.name.clone(), true);
+                self.define(&function.name);
+                self.function(function, FunctionType::Function);
+            }
+            Stmt::If { condition, then_branch, else_branch } =>
+            {
+                self.expression(condition);
+                self.statement(then_branch);
+
+                if let Some(else_branch) = else_branch
+                {
+                    self.statement(else_branch);
+                }
+            }
+            Stmt::Print { expression } => self.expression(expression),
+            Stmt::Return { keyword, value } =>
+            {
+                if self.current_function == FunctionType::None
+                {
+                    self.error(keyword, "Can't return from top-level code.");
+                }
+
+                if let Some(value) = value
+                {
+                    if self.current_function == FunctionType::Initializer
+                    {
+                        self.error(keyword, "Can't return a value from an initializer.");
+                    }
+
+                    self.expression(value);
+                }
+            }
+            Stmt::Var { name, initializer } =>
+            {
+                self.declare(name.clone(), false);
+
+                if let Some(initializer) = initializer
+                {
+                    self.expression(initializer);
+                }
+
+                self.define(name);
+            }
+            Stmt::While { condition, body } =>
+            {
+                self.expression(condition);
+                self.statement(body);
+            }
+            Stmt::For { initializer, condition, increment, body } =>
+            {
+                // The interpreter gives the loop its own scope too
+                self.begin_scope();
+
+                if let Some(initializer) = initializer { self.statement(initializer); }
+                if let Some(condition) = condition { self.expression(condition); }
+                if let Some(increment) = increment { self.expression(increment); }
+                self.statement(body);
+
+                self.end_scope();
+            }
+        }
+    }
+
+    fn class(&mut self, name: &Token, superclass: Option<&Expr>, methods: &[Rc<Function>])
+    {
+        let enclosing_class = self.current_class;
+        self.current_class = ClassType::Class;
+
+        self.declare(name.clone(), true);
+        self.define(name);
+
+        if let Some(superclass) = superclass
+        {
+            if let Expr::Variable { name: superclass_name, .. } = superclass
+            {
+                if superclass_name.lexeme == name.lexeme
+                {
+                    self.error(superclass_name, "A class can't inherit from itself.");
+                }
+            }
+
+            self.current_class = ClassType::Subclass;
+            self.expression(superclass);
+
+            // Matches the environment holding 'parent' that methods close over
+            self.begin_scope();
+            self.implicit(name, "parent");
+        }
+
+        // And the one bind() makes for 'this'
+        self.begin_scope();
+        self.implicit(name, "this");
+
+        for method in methods
+        {
+            let kind = match method.name.lexeme.as_str()
+            {
+                "init" => FunctionType::Initializer,
+                _ => FunctionType::Method,
+            };
+
+            self.function(method, kind);
+        }
+
+        self.end_scope();
+
+        if superclass.is_some()
+        {
+            self.end_scope();
+        }
+
+        self.current_class = enclosing_class;
+    }
+
+    // Parameters and body share a scope, the same as a call's environment
+    fn function(&mut self, function: &Function, kind: FunctionType)
+    {
+        let enclosing_function = self.current_function;
+        self.current_function = kind;
+
+        self.begin_scope();
+
+        for param in &function.params
+        {
+            self.declare(param.clone(), true);
+            self.define(param);
+        }
+
+        self.resolve(&function.body);
+        self.end_scope();
+
+        self.current_function = enclosing_function;
+    }
+
+    fn expression(&mut self, expr: &Expr)
+    {
+        match expr
+        {
+            Expr::Assign { name, value, depth } =>
+            {
+                self.expression(value);
+                self.resolve_local(name, depth, false);
+            }
+            Expr::Binary { left, right, .. } | Expr::Logical { left, right, .. } =>
+            {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expr::Call { callee, arguments, .. } =>
+            {
+                self.expression(callee);
+
+                for argument in arguments
+                {
+                    self.expression(argument);
+                }
+            }
+            // Properties are looked up at runtime, only the object needs resolving
+            Expr::Get { object, .. } => self.expression(object),
+            Expr::Grouping { expression } => self.expression(expression),
+            Expr::Literal { .. } => {},
+            Expr::Set { object, value, .. } =>
+            {
+                self.expression(value);
+                self.expression(object);
+            }
+            Expr::Super { keyword, depth, .. } =>
+            {
+                match self.current_class
+                {
+                    ClassType::None => self.error(keyword, "Can't use 'parent' outside of a class."),
+                    ClassType::Class => self.error(keyword, "Can't use 'parent' in a class with no superclass."),
+                    ClassType::Subclass => self.resolve_local(keyword, depth, true),
+                }
+            }
+            Expr::This { keyword, depth } =>
+            {
+                match self.current_class
+                {
+                    ClassType::None => self.error(keyword, "Can't use 'this' outside of a class."),
+                    _ => self.resolve_local(keyword, depth, true),
+                }
+            }
+            Expr::Unary { right, .. } => self.expression(right),
+            Expr::Variable { name, depth } =>
+            {
+                let declared_only = self.scopes.last()
+                    .and_then(|scope| scope.get(&name.lexeme))
+                    .is_some_and(|local| !local.defined);
+
+                if declared_only
+                {
+                    self.error(name, "Can't read local variable in its own initializer.");
+                }
+
+                self.resolve_local(name, depth, true);
+            }
+        }
+    }
+
+    // Records how many scopes out the name is, leaving globals as None.
+    // Only reads count as a use, assigning to a variable alone doesn't.
+    fn resolve_local(&mut self, name: &Token, depth: &Depth, is_read: bool)
+    {
+        for (distance, scope) in self.scopes.iter_mut().rev().enumerate()
+        {
+            if let Some(local) = scope.get_mut(&name.lexeme)
+            {
+                local.used |= is_read;
+                depth.set(Some(distance));
+                return;
+            }
+        }
+
+        depth.set(None);
+    }
+
+    fn begin_scope(&mut self)
+    {
+        self.scopes.push(HashMap::new());
+    }
+
+    fn end_scope(&mut self)
+    {
+        let scope = match self.scopes.pop()
+        {
+            Some(scope) => scope,
+            None => return,
+        };
+
+        // In source order, so the warnings read top to bottom
+        let mut unused: Vec<Token> = scope.into_values()
+            .filter(|local| !local.used)
+            .map(|local| local.token)
+            .collect();
+
+        unused.sort_by_key(|token| (token.line, token.column));
+
+        for token in unused
+        {
+            self.errors.warning(token.line, format!("at '{}'", token.lexeme), format!("Local variable '{}' is never used.", token.lexeme));
+        }
+    }
+
+    // Globals can be redeclared, locals can't. Anything that isn't a
+    // plain variable starts out used so it never warns.
+    fn declare(&mut self, name: Token, used: bool)
+    {
+        let already_declared = match self.scopes.last()
+        {
+            Some(scope) => scope.contains_key(&name.lexeme),
+            None => return,
+        };
+
+        if already_declared
+        {
+            self.error(&name, "Already a variable with this name in this scope.");
+        }
+
+        if let Some(scope) = self.scopes.last_mut()
+        {
+            scope.insert(name.lexeme.clone(), Local { token: name, defined: false, used });
+        }
+    }
+
+    fn define(&mut self, name: &Token)
+    {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.get_mut(&name.lexeme))
+        {
+            local.defined = true;
+        }
+    }
+
+    // Names the interpreter binds itself, like 'this'
+    fn implicit(&mut self, class: &Token, name: &str)
+    {
+        if let Some(scope) = self.scopes.last_mut()
+        {
+            scope.insert(name.to_string(), Local { token: class.clone(), defined: true, used: true });
+        }
+    }
+
+    fn error(&mut self, token: &Token, message: &str)
+    {
+        let found_at = match token.type_of
+        {
+            TokenType::EOF => "at end".to_string(),
+            _ => format!("at '{}'", token.lexeme),
+        };
+
+        self.errors.report(token.line, found_at, message.to_string());
+    }
+}
@@ -1,274 +1,108 @@
-use std::usize;
-
-use super::{
-    lang::Lang,
-    tokens::{Token, TokenType},
+use crate::lexer::{
+    scanner::{self as lexer, Scanner as Lexer},
+    token::Token as LexerToken,
 };
 
+use super::tokens::{Token, TokenType};
+
+// Gathers the shared lexer's tokens up front, since the parser wants them
+// all at once with their text and values attached.
 pub struct Scanner
 {
-    source: String,
+    lexer: Lexer,
     tokens: Vec<Token>,
-    start: i32,
-    current: i32,
-    line: i32,
-    lang: Lang,
+    // Trivia the parser never sees, kept so the formatter can put it back
+    comments: Vec<Token>,
 }
 
 impl Scanner
 {
     pub fn new() -> Scanner
     {
-        Scanner
-        {
-            source: String::new(),
-            tokens: Vec::new(),
-            start: 0,
-            current: 0,
-            line: 1,
-            lang: Lang::new(),
-        }
+        Scanner::with_source(String::new())
     }
 
     pub fn with_source(source: String) -> Scanner
     {
+        let mut lexer = Lexer::new();
+        lexer.init(source);
+
         Scanner
         {
-            source,
+            lexer,
             tokens: Vec::new(),
-            start: 0,
-            current: 0,
-            line: 1,
-            lang: Lang::new(),
+            comments: Vec::new(),
         }
     }
 
     pub fn set_source(&mut self, source: String)
     {
-        self.source = source;
+        self.lexer.init(source);
+        self.tokens.clear();
+        self.comments.clear();
     }
 
+    // Scans the whole source, reporting every bad token rather than stopping at the first
     pub fn scan_tokens(&mut self) -> Result<&Vec<Token>, String>
     {
-        while !self.is_at_end()
-        {
-            self.start = self.current;
-            match self.scan_token()
-            {
-                Ok(_) => {},
-                Err(_) => return Err(String::from("Failed to parse tokens")),
-            }
-        }
-
-        // Push an EOF and return a ref to tokens generated
-        self.tokens.push(Token::new(TokenType::EOF, String::new(), String::new(), self.line));
-        Ok(&self.tokens)
-    }
-
-    fn substr(&self, start: usize, end: usize) -> &str
-    {
-        &self.source[start as usize..end as usize]
-    }
-
-    fn char_at(&self, pos: usize) -> Option<char>
-    {
-        self.source.chars().nth(pos)
-    }
-
-    fn add_token_lit(&mut self, type_of: TokenType, literal: String)
-    {
-        let text = self.substr(self.start as usize, self.current as usize).to_string();
-        self.tokens.push(Token::new(type_of, text, literal, self.line));
-    }
-
-    fn add_token(&mut self, type_of: TokenType)
-    {
-        self.add_token_lit(type_of, String::new());
-    }
-
-    fn advance(&mut self) -> char
-    {
-        self.current += 1;
-        //println!("Hitting char: {} of {}", self.current, self.source.len());
-        self.char_at(self.current as usize - 1).unwrap()
-    }
-
-    fn peek(&self) -> char
-    {
-        if self.is_at_end() { return '\0' };
-        self.char_at(self.current as usize).unwrap()
-    }
-
-    fn peek_next(&self) -> char
-    {
-        if self.current as usize + 1 >= self.source.len() { return '\0' }
-        self.char_at(self.current as usize + 1).unwrap()
-    }
-
-    fn match_char(&mut self, expected: char) -> bool
-    {
-        if  self.is_at_end() ||
-            self.source.chars().nth(self.current as usize).unwrap() != expected
-        {
-            return false; 
-        }
-
-        self.current += 1;
-        true
-    }
-
-    fn is_digit(character: char) -> bool
-    {
-        character >= '0' && character <= '9'
-    }
-
-    fn is_alpha(character: char) -> bool
-    {
-        (character >= 'a' && character <= 'z') ||
-        (character >= 'A' && character <= 'Z') ||
-        character == '_'
-    }
-
-    fn is_alphanumeric(character: char) -> bool
-    {
-        Scanner::is_alpha(character) || Scanner::is_digit(character)
-    } 
-
-    fn number(&mut self)
-    {
-        while Scanner::is_digit(self.peek()) { self.advance(); }
+        let mut errors = Vec::new();
 
-        if self.peek() == '.' && Scanner::is_digit(self.peek_next())
+        loop
         {
-            self.advance();
+            let token = self.lexer.scan_token();
 
-            while Scanner::is_digit(self.peek()) { self.advance(); }
-        }
-
-        self.add_token_lit(TokenType::Number, self.substr(self.start as usize, self.current as usize).to_string());
-    }
-
-    fn string(&mut self) -> Result<(), String>
-    {
-        while self.peek() != '"' && !self.is_at_end()
-        {
-            if self.peek() == '\n' { self.line += 1; }
-            self.advance();
-        }
-
-        if self.is_at_end()
-        {
-            return Err(format!("Undetermined string on {}", self.line));
-        }
-
-        self.advance();
-
-        let value = self.substr(self.start as usize + 1, self.current as usize - 1).to_string();
-        self.add_token_lit(TokenType::String, value);
-
-        Ok(())
-    }
-
-    fn identifier(&mut self)
-    {
-        while Scanner::is_alphanumeric(self.peek()) { self.advance(); }
-
-        let text = self.substr(self.start as usize, self.current as usize).to_string();
-
-        if let Some(entry) = self.lang.keywords.get_key_value(&text)
-        {
-            // Cant do one-liner since borrow checker complains 
-            let token_type = *entry.1;
-            self.add_token(token_type);
-        }
-        else
-        {
-            self.add_token(TokenType::Identifier);
-        }
-    }
-
-    fn scan_token(&mut self) -> Result<(), String>
-    {
-        use TokenType::*;
-
-        let current_char: char = self.advance();
-
-        match current_char
-        {
-            '(' => self.add_token(LeftParen),
-            ')' => self.add_token(RightParen),
-            '{' => self.add_token(LeftBrace),
-            '}' => self.add_token(RightBrace),
-            ',' => self.add_token(Comma),
-            '.' => self.add_token(Dot),
-            '-' => self.add_token(Minus),
-            '+' => self.add_token(Plus),
-            ';' => self.add_token(Semicolon),
-            '*' => self.add_token(Star),
-            '!' => {
-                let tok = if self.match_char('=') { BangEqual } else { Bang };
-                self.add_token(tok);
-            }
-            '=' => {
-                let tok = if self.match_char('=') { EqualEqual } else { Equal };
-                self.add_token(tok);
-            }
-            '<' => {
-                let tok = if self.match_char('=') { LessEqual } else { Less };
-                self.add_token(tok);
-            }
-            '>' => {
-                let tok = if self.match_char('=') { GreaterEqual } else { Greater };
-                self.add_token(tok);
-            }
-            '/' => {
-                if self.match_char('=') 
-                { 
-                    while self.peek() != '\n' && !self.is_at_end()
-                    {
-                        self.advance();
-                    }
-                } else {  
-                    self.add_token(Slash);
-                };
-            }
-
-            '"' => {
-                match self.string()
+            match token.type_of
+            {
+                TokenType::Error =>
                 {
-                    Err(e) => return Err(e),
-                    _ => {}
+                    errors.push(format!("[line {}:{}] Error: {}", token.line, token.column, self.lexer.error_message()));
                 }
-            }
-
-            // Whitespace
-            ' ' | '\r' | '\t' => {},
-            
-            // Newline
-            '\n' => self.line += 1,
-
-            _ => 
-            {
-                if Scanner::is_digit(current_char)
+                TokenType::Comment =>
                 {
-                    self.number();
+                    let comment = self.convert(&token);
+                    self.comments.push(comment);
                 }
-                else if Scanner::is_alpha(current_char)
+                TokenType::EOF =>
                 {
-                    self.identifier();
+                    let eof = self.convert(&token);
+                    self.tokens.push(eof);
+                    break;
                 }
-                else
+                _ =>
                 {
-                    return Err(format!("Unexpected character {}", current_char)); // Should not hit this :^)
+                    let token = self.convert(&token);
+                    self.tokens.push(token);
                 }
             }
         }
 
-        Ok(())
+        if !errors.is_empty()
+        {
+            return Err(errors.join("\n"));
+        }
+
+        Ok(&self.tokens)
+    }
+
+    // Line comments in source order, valid after scan_tokens
+    pub fn comments(&self) -> &Vec<Token>
+    {
+        &self.comments
     }
 
-    fn is_at_end(&self) -> bool
+    fn convert(&self, token: &LexerToken) -> Token
     {
-        self.current >= self.source.len() as i32
+        let lexeme = self.lexer.lexeme(token);
+
+        let (lexeme, literal) = match token.type_of
+        {
+            // The lexer has already checked the escapes
+            TokenType::String => (lexeme, lexer::unescape(&lexeme[1..lexeme.len() - 1]).unwrap_or_default()),
+            TokenType::Number => (lexeme, lexeme.to_string()),
+            TokenType::Comment => (lexeme.trim_end(), String::new()),
+            _ => (lexeme, String::new()),
+        };
+
+        Token::new(token.type_of, lexeme.to_string(), literal, token.line as i32, token.column as i32)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,32 @@
+use std::rc::Rc;
+
+use super::{
+    expr::Expr,
+    tokens::Token,
+};
+
+#[derive(Debug)]
+pub struct Function
+{
+    pub name: Token,
+    pub params: Vec<Token>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Stmt
+{
+    Block { statements: Vec<Stmt> },
+    // The superclass is always an Expr::Variable
+    Class { name: Token, superclass: Option<Expr>, methods: Vec<Rc<Function>> },
+    Expression { expression: Expr },
+    // Shared, since closures created from it outlive the statement
+    Function { function: Rc<Function> },
+    If { condition: Expr, then_branch: Box<Stmt>, else_branch: Option<Box<Stmt>> },
+    Print { expression: Expr },
+    Return { keyword: Token, value: Option<Expr> },
+    Var { name: Token, initializer: Option<Expr> },
+    While { condition: Expr, body: Box<Stmt> },
+    // Kept as written rather than lowered to a while, so it can be printed back
+    For { initializer: Option<Box<Stmt>>, condition: Option<Expr>, increment: Option<Expr>, body: Box<Stmt> },
+}
@@ -1,45 +1,28 @@
-#[derive(Debug, Copy, Clone)]
-pub enum TokenType
-{
-    // Single-character tokens.
-    LeftParen, RightParen, LeftBrace, RightBrace,
-    Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
-
-    // One or two character tokens.
-    Bang, BangEqual,
-    Equal, EqualEqual,
-    Greater, GreaterEqual,
-    Less, LessEqual,
-
-    // Literals.
-    Identifier, String, Number,
-
-    // Keywords.
-    And, Class, Else, False, Func, For, If, Null, Or,
-    Print, Return, Super, This, True, Var, While,
-
-    EOF
-}
-
+// One set of token types for both front ends
+pub use crate::lexer::token::TokenType;
 
+#[derive(Debug, Clone)]
 pub struct Token
 {
     pub type_of: TokenType,
     pub lexeme: String,
     pub literal: String,
     pub line: i32,
+    // Counted in characters from 1, not bytes
+    pub column: i32,
 }
 
 impl Token
 {
-    pub fn new(type_of: TokenType, lexeme: String, literal: String, line: i32) -> Token
+    pub fn new(type_of: TokenType, lexeme: String, literal: String, line: i32, column: i32) -> Token
     {
         Token
         {
             type_of,
             lexeme,
             literal,
-            line
+            line,
+            column,
         }
     }
 
@@ -0,0 +1,158 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+use super::{
+    environment::Environment,
+    interpreter::Interpreter,
+    stmt::Function,
+};
+
+#[derive(Clone)]
+pub enum Value
+{
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<LoxFunction>),
+    Native(Rc<NativeFunction>),
+    Class(Rc<LoxClass>),
+    Instance(Rc<RefCell<LoxInstance>>),
+}
+
+// Natives may fail with a runtime error message
+pub type NativeFn = fn(&mut Interpreter, &[Value]) -> Result<Value, String>;
+
+pub struct NativeFunction
+{
+    pub name: String,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+// A function declaration together with the scope it was declared in
+pub struct LoxFunction
+{
+    pub declaration: Rc<Function>,
+    pub closure: Rc<RefCell<Environment>>,
+    pub is_initializer: bool,
+}
+
+pub struct LoxClass
+{
+    pub name: String,
+    pub superclass: Option<Rc<LoxClass>>,
+    pub methods: HashMap<String, Rc<LoxFunction>>,
+}
+
+pub struct LoxInstance
+{
+    pub class: Rc<LoxClass>,
+    pub fields: HashMap<String, Value>,
+}
+
+impl Value
+{
+    // null and false are the only falsey values, everything else is truthy
+    pub fn is_falsey(&self) -> bool
+    {
+        match self
+        {
+            Value::Null => true,
+            Value::Bool(value) => !value,
+            _ => false,
+        }
+    }
+}
+
+// Strings compare by content, everything on the heap by identity
+impl PartialEq for Value
+{
+    fn eq(&self, other: &Value) -> bool
+    {
+        use Value::*;
+        match (self, other)
+        {
+            (Null, Null) => true,
+            (Bool(a), Bool(b)) => a == b,
+            (Number(a), Number(b)) => a == b,
+            (String(a), String(b)) => a == b,
+            (Function(a), Function(b)) => Rc::ptr_eq(a, b),
+            (Native(a), Native(b)) => Rc::ptr_eq(a, b),
+            (Class(a), Class(b)) => Rc::ptr_eq(a, b),
+            (Instance(a), Instance(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+// Matches how the bytecode VM prints its values
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Value::Null => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::String(value) => write!(f, "{}", value),
+            Value::Function(function) => write!(f, "<fn {}>", function.declaration.name.lexeme),
+            Value::Native(_) => write!(f, "<native fn>"),
+            Value::Class(class) => write!(f, "{}", class.name),
+            Value::Instance(instance) => write!(f, "{} instance", instance.borrow().class.name),
+        }
+    }
+}
+
+impl fmt::Debug for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self)
+    }
+}
+
+impl LoxFunction
+{
+    pub fn arity(&self) -> usize
+    {
+        self.declaration.params.len()
+    }
+
+    // A copy of the method whose scope has 'this' set to the instance
+    pub fn bind(&self, instance: Rc<RefCell<LoxInstance>>) -> LoxFunction
+    {
+        let mut environment = Environment::with_enclosing(self.closure.clone());
+        environment.define("this", Value::Instance(instance));
+
+        LoxFunction
+        {
+            declaration: self.declaration.clone(),
+            closure: Rc::new(RefCell::new(environment)),
+            is_initializer: self.is_initializer,
+        }
+    }
+}
+
+impl LoxClass
+{
+    // Walks up the superclass chain
+    pub fn find_method(&self, name: &str) -> Option<Rc<LoxFunction>>
+    {
+        match self.methods.get(name)
+        {
+            Some(method) => Some(method.clone()),
+            None => self.superclass.as_ref().and_then(|superclass| superclass.find_method(name)),
+        }
+    }
+
+    pub fn arity(&self) -> usize
+    {
+        self.find_method("init").map_or(0, |init| init.arity())
+    }
+}
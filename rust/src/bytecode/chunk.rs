@@ -4,12 +4,59 @@ use super::value::{Value, ValueArray};
 pub enum OpCode
 {
     Constant,
+    // Same as Constant, with a 24-bit big endian operand
+    ConstantLong,
+    Nil,
+    True,
+    False,
+    Equal,
+    Greater,
+    Less,
+    Not,
     Negate,
     Add,
     Subtract,
     Multiply,
     Divide,
+    Pop,
+    GetLocal,
+    SetLocal,
+    GetGlobal,
+    DefineGlobal,
+    SetGlobal,
+    GetUpvalue,
+    SetUpvalue,
+    GetProperty,
+    SetProperty,
+    GetSuper,
+    Print,
+    Jump,
+    JumpIfFalse,
+    Loop,
+    Call,
+    Invoke,
+    SuperInvoke,
+    Closure,
+    CloseUpvalue,
     Return,
+    Class,
+    Inherit,
+    Method,
+    // Not Less and Not Greater would be true for NaN
+    GreaterEqual,
+    LessEqual,
+    // Long forms of the instructions taking a constant index, see ConstantLong
+    GetGlobalLong,
+    DefineGlobalLong,
+    SetGlobalLong,
+    GetPropertyLong,
+    SetPropertyLong,
+    GetSuperLong,
+    InvokeLong,
+    SuperInvokeLong,
+    ClosureLong,
+    ClassLong,
+    MethodLong,
 
     Unknown,
 }
@@ -21,12 +68,56 @@ impl From<u8> for OpCode
         match orig
         {
             0 => Self::Constant,
-            1 => Self::Negate,
-            2 => Self::Add,
-            3 => Self::Subtract,
-            4 => Self::Multiply,
-            5 => Self::Divide,
-            6 => Self::Return,
+            1 => Self::ConstantLong,
+            2 => Self::Nil,
+            3 => Self::True,
+            4 => Self::False,
+            5 => Self::Equal,
+            6 => Self::Greater,
+            7 => Self::Less,
+            8 => Self::Not,
+            9 => Self::Negate,
+            10 => Self::Add,
+            11 => Self::Subtract,
+            12 => Self::Multiply,
+            13 => Self::Divide,
+            14 => Self::Pop,
+            15 => Self::GetLocal,
+            16 => Self::SetLocal,
+            17 => Self::GetGlobal,
+            18 => Self::DefineGlobal,
+            19 => Self::SetGlobal,
+            20 => Self::GetUpvalue,
+            21 => Self::SetUpvalue,
+            22 => Self::GetProperty,
+            23 => Self::SetProperty,
+            24 => Self::GetSuper,
+            25 => Self::Print,
+            26 => Self::Jump,
+            27 => Self::JumpIfFalse,
+            28 => Self::Loop,
+            29 => Self::Call,
+            30 => Self::Invoke,
+            31 => Self::SuperInvoke,
+            32 => Self::Closure,
+            33 => Self::CloseUpvalue,
+            34 => Self::Return,
+            35 => Self::Class,
+            36 => Self::Inherit,
+            37 => Self::Method,
+            38 => Self::GreaterEqual,
+            39 => Self::LessEqual,
+            40 => Self::GetGlobalLong,
+            41 => Self::DefineGlobalLong,
+            42 => Self::SetGlobalLong,
+            43 => Self::GetPropertyLong,
+            44 => Self::SetPropertyLong,
+            45 => Self::GetSuperLong,
+            46 => Self::InvokeLong,
+            47 => Self::SuperInvokeLong,
+            48 => Self::ClosureLong,
+            49 => Self::ClassLong,
+            50 => Self::MethodLong,
             _ => Self::Unknown,
         }
     }
@@ -40,21 +131,123 @@ impl From<OpCode> for u8
         match orig
         {
             Constant => 0,
-            Negate => 1,
-            Add => 2,
-            Subtract => 3,
-            Multiply => 4,
-            Divide => 5,
-            Return => 6,
-            _ => 7,
+            ConstantLong => 1,
+            Nil => 2,
+            True => 3,
+            False => 4,
+            Equal => 5,
+            Greater => 6,
+            Less => 7,
+            Not => 8,
+            Negate => 9,
+            Add => 10,
+            Subtract => 11,
+            Multiply => 12,
+            Divide => 13,
+            Pop => 14,
+            GetLocal => 15,
+            SetLocal => 16,
+            GetGlobal => 17,
+            DefineGlobal => 18,
+            SetGlobal => 19,
+            GetUpvalue => 20,
+            SetUpvalue => 21,
+            GetProperty => 22,
+            SetProperty => 23,
+            GetSuper => 24,
+            Print => 25,
+            Jump => 26,
+            JumpIfFalse => 27,
+            Loop => 28,
+            Call => 29,
+            Invoke => 30,
+            SuperInvoke => 31,
+            Closure => 32,
+            CloseUpvalue => 33,
+            Return => 34,
+            Class => 35,
+            Inherit => 36,
+            Method => 37,
+            GreaterEqual => 38,
+            LessEqual => 39,
+            GetGlobalLong => 40,
+            DefineGlobalLong => 41,
+            SetGlobalLong => 42,
+            GetPropertyLong => 43,
+            SetPropertyLong => 44,
+            GetSuperLong => 45,
+            InvokeLong => 46,
+            SuperInvokeLong => 47,
+            ClosureLong => 48,
+            ClassLong => 49,
+            MethodLong => 50,
+            _ => 51,
         }
     }
 }
 
+impl OpCode
+{
+    // The same instruction with a 24-bit operand, for constant indexes past 255
+    pub fn long_form(self) -> OpCode
+    {
+        use OpCode::*;
+        match self
+        {
+            Constant => ConstantLong,
+            GetGlobal => GetGlobalLong,
+            DefineGlobal => DefineGlobalLong,
+            SetGlobal => SetGlobalLong,
+            GetProperty => GetPropertyLong,
+            SetProperty => SetPropertyLong,
+            GetSuper => GetSuperLong,
+            Invoke => InvokeLong,
+            SuperInvoke => SuperInvokeLong,
+            Closure => ClosureLong,
+            Class => ClassLong,
+            Method => MethodLong,
+            _ => unreachable!("{:?} has no long form", self),
+        }
+    }
+
+    // The instruction a long form widens, and whether it was one
+    pub fn short_form(self) -> (OpCode, bool)
+    {
+        use OpCode::*;
+        match self
+        {
+            ConstantLong => (Constant, true),
+            GetGlobalLong => (GetGlobal, true),
+            DefineGlobalLong => (DefineGlobal, true),
+            SetGlobalLong => (SetGlobal, true),
+            GetPropertyLong => (GetProperty, true),
+            SetPropertyLong => (SetProperty, true),
+            GetSuperLong => (GetSuper, true),
+            InvokeLong => (Invoke, true),
+            SuperInvokeLong => (SuperInvoke, true),
+            ClosureLong => (Closure, true),
+            ClassLong => (Class, true),
+            MethodLong => (Method, true),
+            _ => (self, false),
+        }
+    }
+}
+
+// Source position shared by a run of bytes, starting at offset and lasting
+// until the next run. Kept as u32 as there is one per instruction in the worst case.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LineRun
+{
+    pub offset: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
 pub struct Chunk
 {
     pub(super) code: Vec<u8>,
-    pub(super) lines: Vec<usize>,
+    // Run-length encoded, ordered by offset
+    pub(super) lines: Vec<LineRun>,
     pub(super) constants: ValueArray,
 }
 
@@ -70,16 +263,51 @@ impl Chunk
         }
     }
 
-    pub fn write(&mut self, byte: OpCode, line: usize)
+    pub fn write(&mut self, byte: OpCode, line: usize, column: usize)
     {
         self.code.push(byte as u8);
-        self.lines.push(line);
+        self.add_line(line, column);
     }
 
-    pub fn write_constant(&mut self, byte: usize, line: usize)
+    pub fn write_constant(&mut self, byte: usize, line: usize, column: usize)
     {
         self.code.push(byte as u8);
-        self.lines.push(line);
+        self.add_line(line, column);
+    }
+
+    // Only starts a new run when the position changes
+    fn add_line(&mut self, line: usize, column: usize)
+    {
+        if let Some(last) = self.lines.last()
+        {
+            if last.line as usize == line && last.column as usize == column { return; }
+        }
+
+        self.lines.push(LineRun
+        {
+            offset: (self.code.len() - 1) as u32,
+            line: line as u32,
+            column: column as u32,
+        });
+    }
+
+    fn find_run(&self, offset: usize) -> Option<&LineRun>
+    {
+        // Index of the first run starting past offset, the one before it covers offset
+        let index = self.lines.partition_point(|run| run.offset as usize <= offset);
+        if index == 0 { return None; }
+
+        self.lines.get(index - 1)
+    }
+
+    pub fn get_line(&self, offset: usize) -> usize
+    {
+        self.find_run(offset).map_or(0, |run| run.line as usize)
+    }
+
+    pub fn get_column(&self, offset: usize) -> usize
+    {
+        self.find_run(offset).map_or(0, |run| run.column as usize)
     }
 
     pub fn add_constant(&mut self, value: Value) -> usize
@@ -1,8 +1,23 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    ops::Range,
+};
+
+use crate::{
+    diagnostics,
+    lexer::{
+        scanner::{self, Scanner},
+        token::{Span, Token, TokenType},
+    },
+};
+
 use super::{
     debug,
     value::Value,
+    vm::VM,
     chunk::{Chunk, OpCode},
-    scanner::{Scanner, Token, TokenType},
+    object::ObjFunction,
 };
 
 #[derive(Debug, Copy, Clone)]
@@ -21,77 +36,207 @@ enum Precedence
     Primary,
 }
 
+const LOCALS_MAX: usize = u8::MAX as usize + 1;
+// Largest index a ConstantLong operand can hold
+const CONSTANTS_MAX: usize = 0xff_ffff;
+
+// A single diagnostic reported while compiling. The span is a byte range
+// into the source, the lexeme is empty when the error is at the end of input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompileError
+{
+    pub line: usize,
+    pub column: usize,
+    pub span: Range<usize>,
+    pub lexeme: String,
+    pub message: String,
+    // The source stopped short rather than being wrong, more of it could fix the error
+    pub incomplete: bool,
+}
+
+impl fmt::Display for CompileError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "[line {}:{}] Error", self.line, self.column)?;
+
+        if self.lexeme.is_empty()
+        {
+            write!(f, " at end")?;
+        }
+        else
+        {
+            write!(f, " at '{}'", self.lexeme)?;
+        }
+
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl CompileError
+{
+    // The offending line with the whole lexeme underlined
+    pub fn excerpt(&self, source: &str) -> String
+    {
+        let width = source.get(self.span.clone()).map_or(1, |lexeme| lexeme.chars().count());
+        diagnostics::excerpt(source, self.line, self.column, width)
+    }
+}
+
+type ParseFn<'a> = fn(&mut Parser<'a>, bool);
+
 #[derive(Copy, Clone)]
-struct ParseRule<F>
-    where F: for<'r> Fn(&'r mut Parser)
+struct ParseRule<'a>
 {
-    prefix: Option<F>,
-    infix: Option<F>,
+    prefix: Option<ParseFn<'a>>,
+    infix: Option<ParseFn<'a>>,
     precedence: Precedence,
 }
 
-pub struct Parser
+#[derive(Clone)]
+struct Local
+{
+    name: String,
+    // -1 marks a local that has been declared but not yet initialised
+    depth: i32,
+    is_captured: bool,
+}
+
+#[derive(Copy, Clone)]
+struct Upvalue
+{
+    index: u8,
+    // Whether this captures a local of the enclosing function or one of its upvalues
+    is_local: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum FunctionType
+{
+    Function,
+    Initializer,
+    Method,
+    Script,
+}
+
+struct ClassCompiler
+{
+    has_superclass: bool,
+}
+
+// Per-function compiler state, nested function declarations push a new one
+struct Compiler
+{
+    function: ObjFunction,
+    type_of: FunctionType,
+    locals: Vec<Local>,
+    upvalues: Vec<Upvalue>,
+    scope_depth: i32,
+    // Constant index of each name mentioned so far, so repeats share one
+    names: HashMap<String, usize>,
+}
+
+impl Compiler
+{
+    fn new(type_of: FunctionType) -> Compiler
+    {
+        let mut locals = Vec::with_capacity(LOCALS_MAX);
+
+        // Slot zero is reserved for the function being called, or the receiver in methods
+        let name = match type_of
+        {
+            FunctionType::Function | FunctionType::Script => String::new(),
+            FunctionType::Initializer | FunctionType::Method => "this".to_string(),
+        };
+
+        locals.push(Local
+        {
+            name,
+            depth: 0,
+            is_captured: false,
+        });
+
+        Compiler
+        {
+            function: ObjFunction::new(),
+            type_of,
+            locals,
+            upvalues: Vec::new(),
+            scope_depth: 0,
+            names: HashMap::new(),
+        }
+    }
+}
+
+pub struct Parser<'a>
 {
     current: Token,
     previous: Token,
-    chunk: Chunk,
     had_error: bool,
     panic_mode: bool,
+    errors: Vec<CompileError>,
     scanner: Scanner,
-    rules: Vec<ParseRule<Box<dyn Fn(&mut Parser)>>>,
+    rules: Vec<ParseRule<'a>>,
+    vm: &'a mut VM,
+    compilers: Vec<Compiler>,
+    class_compilers: Vec<ClassCompiler>,
 }
 
-impl Parser
+impl<'a> Parser<'a>
 {
-    pub fn new() -> Parser
+    pub fn new(vm: &'a mut VM) -> Parser<'a>
     {
         Parser
         {
-            current: Token { type_of: TokenType::EOF, start: 0, length: 0, line: 0 },
-            previous: Token { type_of: TokenType::EOF, start: 0, length: 0, line: 0 },
-            chunk: Chunk::new(),
+            current: Token { type_of: TokenType::EOF, span: Span { start: 0, end: 0 }, line: 0, column: 0 },
+            previous: Token { type_of: TokenType::EOF, span: Span { start: 0, end: 0 }, line: 0, column: 0 },
             had_error: false,
             panic_mode: false,
+            errors: Vec::new(),
             scanner: Scanner::new(),
+            vm,
+            compilers: Vec::new(),
+            class_compilers: Vec::new(),
             rules: vec![
-                /* LeftParen */     ParseRule { prefix: Some(Box::new(Self::grouping)), infix: None, precedence: Precedence::None },
+                /* LeftParen */     ParseRule { prefix: Some(Self::grouping), infix: Some(Self::call), precedence: Precedence::Call },
                 /* RightParen */    ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* LeftBrace */     ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* RightBrace */    ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* Comma */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Dot */           ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Minus */         ParseRule { prefix: Some(Box::new(Self::unary)), infix: Some(Box::new(Self::binary)), precedence: Precedence::Term },
-                /* Plus */          ParseRule { prefix: None, infix: Some(Box::new(Self::binary)), precedence: Precedence::Term  },
+                /* Dot */           ParseRule { prefix: None, infix: Some(Self::dot), precedence: Precedence::Call },
+                /* Minus */         ParseRule { prefix: Some(Self::unary), infix: Some(Self::binary), precedence: Precedence::Term },
+                /* Plus */          ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Term  },
                 /* Semicolon */     ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Slash */         ParseRule { prefix: None, infix: Some(Box::new(Self::binary)), precedence: Precedence::Factor  },
-                /* Star */          ParseRule { prefix: None, infix: Some(Box::new(Self::binary)), precedence: Precedence::Factor  },
-                /* Bang */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* BangEqual */     ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* Slash */         ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Factor  },
+                /* Star */          ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Factor  },
+                /* Bang */          ParseRule { prefix: Some(Self::unary), infix: None, precedence: Precedence::None },
+                /* BangEqual */     ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Equality },
                 /* Equal */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* EqualEqual */    ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Greater */       ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* GreaterEqaul */  ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Less */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* LessEqual */     ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Identifier */    ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* String */        ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Number */        ParseRule { prefix: Some(Box::new(Self::number)), infix: None, precedence: Precedence::None },
-                /* And */           ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* EqualEqual */    ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Equality },
+                /* Greater */       ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Comparison },
+                /* GreaterEqaul */  ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Comparison },
+                /* Less */          ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Comparison },
+                /* LessEqual */     ParseRule { prefix: None, infix: Some(Self::binary), precedence: Precedence::Comparison },
+                /* Identifier */    ParseRule { prefix: Some(Self::variable), infix: None, precedence: Precedence::None },
+                /* String */        ParseRule { prefix: Some(Self::string), infix: None, precedence: Precedence::None },
+                /* Number */        ParseRule { prefix: Some(Self::number), infix: None, precedence: Precedence::None },
+                /* And */           ParseRule { prefix: None, infix: Some(Self::and), precedence: Precedence::And },
                 /* Class */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* Else */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* False */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* False */         ParseRule { prefix: Some(Self::literal), infix: None, precedence: Precedence::None },
                 /* For */           ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* Func */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* If */            ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Null */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Or */            ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* Null */          ParseRule { prefix: Some(Self::literal), infix: None, precedence: Precedence::None },
+                /* Or */            ParseRule { prefix: None, infix: Some(Self::or), precedence: Precedence::Or },
                 /* Print */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* Return */        ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* Super */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* This */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
-                /* True */          ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* Super */         ParseRule { prefix: Some(Self::super_), infix: None, precedence: Precedence::None },
+                /* This */          ParseRule { prefix: Some(Self::this), infix: None, precedence: Precedence::None },
+                /* True */          ParseRule { prefix: Some(Self::literal), infix: None, precedence: Precedence::None },
                 /* Var */           ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* While */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
+                /* Comment */       ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* Error */         ParseRule { prefix: None, infix: None, precedence: Precedence::None },
                 /* EOF */           ParseRule { prefix: None, infix: None, precedence: Precedence::None },
             ],
@@ -105,46 +250,89 @@ impl Parser
         loop
         {
             self.current = self.scanner.scan_token();
-            if self.current.type_of != TokenType::Error { break; }
 
-            self.error_at_current(format!("{}", self.current.start));
+            match self.current.type_of
+            {
+                // Comments only matter to the formatter
+                TokenType::Comment => continue,
+                TokenType::Error =>
+                {
+                    let message = self.scanner.error_message().to_string();
+                    self.error_at_current(message);
+                }
+                _ => break,
+            }
         }
     }
 
-    pub fn compile(&mut self, source: String, chunk: Chunk) -> bool
+    // Compiles the whole source, collecting every error found along the way
+    pub fn compile(&mut self, source: String, chunk: Chunk) -> Result<Chunk, Vec<CompileError>>
     {
         self.scanner.init(source);
 
-        self.chunk = chunk;
+        let mut script = Compiler::new(FunctionType::Script);
+        script.function.chunk = chunk;
+        self.compilers.push(script);
 
         self.had_error = false;
         self.panic_mode = false;
+        self.errors.clear();
 
         self.advance();
-        self.expression();
-        self.consume(TokenType::EOF, "Expect end of expression.".to_string());
 
-        self.end_compiler();
-        !self.had_error
+        while !self.match_type(TokenType::EOF)
+        {
+            self.declaration();
+        }
+
+        let script = self.end_compiler();
+        self.vm.compiler_roots.clear();
+
+        if self.had_error
+        {
+            return Err(std::mem::take(&mut self.errors));
+        }
+
+        Ok(script.chunk)
+    }
+
+    fn current(&self) -> &Compiler
+    {
+        self.compilers.last().unwrap()
+    }
+
+    fn current_mut(&mut self) -> &mut Compiler
+    {
+        self.compilers.last_mut().unwrap()
+    }
+
+    fn current_chunk(&mut self) -> &mut Chunk
+    {
+        &mut self.current_mut().function.chunk
     }
 
-    fn end_compiler(&mut self)
+    fn end_compiler(&mut self) -> ObjFunction
     {
         self.emit_return();
 
+        let mut compiler = self.compilers.pop().unwrap();
+        compiler.function.upvalue_count = compiler.upvalues.len();
+
         if cfg!(print_code = "true")
         {
             if !self.had_error
             {
-                debug::disassemble(&self.chunk, "code".to_string());
+                debug::disassemble(&compiler.function.chunk, format!("{}", compiler.function));
             }
         }
+
+        compiler.function
     }
 
-    fn binary(&mut self)
+    fn binary(&mut self, _can_assign: bool)
     {
-        let operator_type = self.previous.type_of;
-        let precedence = self.get_rule_precedence(operator_type);
+        let operator = self.previous;
+        let precedence = self.get_rule_precedence(operator.type_of);
 
         self.parse_precedence(match precedence
         {
@@ -161,15 +349,24 @@ impl Parser
             Precedence::Primary => Precedence::None,
         });
 
-        use TokenType::*;
-        match operator_type
+        let (instruction, negate) = match operator.type_of
         {
-            Plus => self.emit_byte(OpCode::Add),
-            Minus => self.emit_byte(OpCode::Subtract),
-            Star => self.emit_byte(OpCode::Multiply),
-            Slash => self.emit_byte(OpCode::Divide),
+            TokenType::BangEqual => (OpCode::Equal, true),
+            TokenType::EqualEqual => (OpCode::Equal, false),
+            TokenType::Greater => (OpCode::Greater, false),
+            TokenType::GreaterEqual => (OpCode::GreaterEqual, false),
+            TokenType::Less => (OpCode::Less, false),
+            TokenType::LessEqual => (OpCode::LessEqual, false),
+            TokenType::Plus => (OpCode::Add, false),
+            TokenType::Minus => (OpCode::Subtract, false),
+            TokenType::Star => (OpCode::Multiply, false),
+            TokenType::Slash => (OpCode::Divide, false),
             _ => unimplemented!(), // Unreachable
-        }
+        };
+
+        // Runtime errors should point at the operator, not the end of the right operand
+        self.emit_byte_at(instruction, operator);
+        if negate { self.emit_byte_at(OpCode::Not, operator); }
     }
 
     fn expression(&mut self)
@@ -177,44 +374,591 @@ impl Parser
         self.parse_precedence(Precedence::Assignment);
     }
 
-    fn parse_precedence(&mut self, precedence: Precedence)
+    fn declaration(&mut self)
     {
-        self.advance();
+        if self.match_type(TokenType::Class)
+        {
+            self.class_declaration();
+        }
+        else if self.match_type(TokenType::Func)
+        {
+            self.fun_declaration();
+        }
+        else if self.match_type(TokenType::Var)
+        {
+            self.var_declaration();
+        }
+        else
+        {
+            self.statement();
+        }
+
+        if self.panic_mode { self.synchronize(); }
+    }
+
+    fn class_declaration(&mut self)
+    {
+        self.consume(TokenType::Identifier, "Expect class name.".to_string());
+        let class_name = self.lexeme(&self.previous);
+        let name_constant = self.identifier_constant(&class_name);
+        self.declare_variable();
 
-        //* Borrows occur and we need to not, prefix and infix */
-        //* calls are also not recommended in stable. */
+        self.emit_indexed(OpCode::Class, name_constant);
+        self.define_variable(name_constant);
 
-        let type_of = self.previous.type_of;
-        if let Some(prefix) = self.get_rule(type_of).prefix
+        self.class_compilers.push(ClassCompiler { has_superclass: false });
+
+        if self.match_type(TokenType::Less)
         {
-            //? Find better fix, this is unsafe
-            prefix.call((self,));
+            self.consume(TokenType::Identifier, "Expect superclass name.".to_string());
+            self.variable(false);
 
-            while precedence as usize <= self.get_rule_precedence(self.previous.type_of) as usize
+            if class_name == self.lexeme(&self.previous)
             {
-                self.advance();
-                
-                if let Some(infix) = self.get_rule_infix(type_of)
+                self.error("A class can't inherit from itself.".to_string());
+            }
+
+            // The superclass lives in a local named after the keyword, so users can't shadow it
+            self.begin_scope();
+            self.add_local("parent".to_string());
+            self.define_variable(0);
+
+            self.named_variable(&class_name, false);
+            self.emit_byte(OpCode::Inherit);
+            self.class_compilers.last_mut().unwrap().has_superclass = true;
+        }
+
+        // Load the class back onto the stack so methods can be bound to it
+        self.named_variable(&class_name, false);
+
+        self.consume(TokenType::LeftBrace, "Expect '{' before class body.".to_string());
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF)
+        {
+            self.method();
+        }
+        self.consume(TokenType::RightBrace, "Expect '}' after class body.".to_string());
+        self.emit_byte(OpCode::Pop);
+
+        if self.class_compilers.pop().unwrap().has_superclass
+        {
+            self.end_scope();
+        }
+    }
+
+    fn method(&mut self)
+    {
+        self.consume(TokenType::Identifier, "Expect method name.".to_string());
+        let name = self.lexeme(&self.previous);
+        let constant = self.identifier_constant(&name);
+
+        let type_of = if name == "init" { FunctionType::Initializer } else { FunctionType::Method };
+        self.function(type_of);
+
+        self.emit_indexed(OpCode::Method, constant);
+    }
+
+    fn fun_declaration(&mut self)
+    {
+        let global = self.parse_variable("Expect function name.".to_string());
+
+        // Functions can refer to themselves, so they are usable before the body is compiled
+        self.mark_initialized();
+        self.function(FunctionType::Function);
+        self.define_variable(global);
+    }
+
+    fn function(&mut self, type_of: FunctionType)
+    {
+        let mut compiler = Compiler::new(type_of);
+        let name = self.lexeme(&self.previous);
+        let name = self.vm.copy_string(&name);
+        self.vm.compiler_roots.push(name);
+        compiler.function.name = Some(name);
+        self.compilers.push(compiler);
+
+        self.begin_scope();
+
+        self.consume(TokenType::LeftParen, "Expect '(' after function name.".to_string());
+
+        if !self.check(TokenType::RightParen)
+        {
+            loop
+            {
+                self.current_mut().function.arity += 1;
+                if self.current().function.arity > u8::MAX as usize
                 {
-                    infix.call((self,));
+                    self.error_at_current("Can't have more than 255 parameters.".to_string());
                 }
+
+                let constant = self.parse_variable("Expect parameter name.".to_string());
+                self.define_variable(constant);
+
+                if !self.match_type(TokenType::Comma) { break; }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.".to_string());
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.".to_string());
+        self.block();
+
+        // No end_scope, the whole frame is discarded when the function returns
+        let upvalues = self.current().upvalues.clone();
+        let function = self.end_compiler();
+        let function = self.vm.new_function(function);
+
+        let constant = self.add_constant(Value::Obj(function));
+        self.emit_indexed(OpCode::Closure, constant);
+
+        for upvalue in upvalues
+        {
+            self.emit_operand(if upvalue.is_local { 1 } else { 0 });
+            self.emit_operand(upvalue.index);
+        }
+    }
+
+    fn var_declaration(&mut self)
+    {
+        let global = self.parse_variable("Expect variable name.".to_string());
+
+        if self.match_type(TokenType::Equal)
+        {
+            self.expression();
+        }
+        else
+        {
+            self.emit_byte(OpCode::Nil);
+        }
+
+        self.consume(TokenType::Semicolon, "Expect ';' after variable declaration.".to_string());
+        self.define_variable(global);
+    }
+
+    fn statement(&mut self)
+    {
+        if self.match_type(TokenType::Print)
+        {
+            self.print_statement();
+        }
+        else if self.match_type(TokenType::For)
+        {
+            self.for_statement();
+        }
+        else if self.match_type(TokenType::If)
+        {
+            self.if_statement();
+        }
+        else if self.match_type(TokenType::Return)
+        {
+            self.return_statement();
+        }
+        else if self.match_type(TokenType::While)
+        {
+            self.while_statement();
+        }
+        else if self.match_type(TokenType::LeftBrace)
+        {
+            self.begin_scope();
+            self.block();
+            self.end_scope();
+        }
+        else
+        {
+            self.expression_statement();
+        }
+    }
+
+    fn block(&mut self)
+    {
+        while !self.check(TokenType::RightBrace) && !self.check(TokenType::EOF)
+        {
+            self.declaration();
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after block.".to_string());
+    }
+
+    fn begin_scope(&mut self)
+    {
+        self.current_mut().scope_depth += 1;
+    }
+
+    fn end_scope(&mut self)
+    {
+        self.current_mut().scope_depth -= 1;
+
+        // Discard every local that was declared in the scope we just left
+        while let Some(local) = self.current().locals.last()
+        {
+            if local.depth <= self.current().scope_depth { break; }
+
+            if local.is_captured
+            {
+                self.emit_byte(OpCode::CloseUpvalue);
+            }
+            else
+            {
+                self.emit_byte(OpCode::Pop);
+            }
+            self.current_mut().locals.pop();
+        }
+    }
+
+    fn if_statement(&mut self)
+    {
+        self.consume(TokenType::LeftParen, "Expect '(' after 'if'.".to_string());
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after condition.".to_string());
+
+        let then_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_byte(OpCode::Pop);
+        self.statement();
+
+        let else_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(then_jump);
+        self.emit_byte(OpCode::Pop);
+
+        if self.match_type(TokenType::Else) { self.statement(); }
+        self.patch_jump(else_jump);
+    }
+
+    fn return_statement(&mut self)
+    {
+        if self.current().type_of == FunctionType::Script
+        {
+            self.error("Can't return from top-level code.".to_string());
+        }
+
+        if self.match_type(TokenType::Semicolon)
+        {
+            self.emit_return();
+        }
+        else
+        {
+            if self.current().type_of == FunctionType::Initializer
+            {
+                self.error("Can't return a value from an initializer.".to_string());
+            }
+
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after return value.".to_string());
+            self.emit_byte(OpCode::Return);
+        }
+    }
+
+    fn while_statement(&mut self)
+    {
+        let loop_start = self.current_chunk().code.len();
+
+        self.consume(TokenType::LeftParen, "Expect '(' after 'while'.".to_string());
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after condition.".to_string());
+
+        let exit_jump = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_byte(OpCode::Pop);
+        self.statement();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_byte(OpCode::Pop);
+    }
+
+    fn for_statement(&mut self)
+    {
+        self.begin_scope();
+        self.consume(TokenType::LeftParen, "Expect '(' after 'for'.".to_string());
+
+        // Initializer
+        if self.match_type(TokenType::Semicolon)
+        {
+            // No initializer
+        }
+        else if self.match_type(TokenType::Var)
+        {
+            self.var_declaration();
+        }
+        else
+        {
+            self.expression_statement();
+        }
+
+        let mut loop_start = self.current_chunk().code.len();
+
+        // Condition
+        let mut exit_jump = None;
+        if !self.match_type(TokenType::Semicolon)
+        {
+            self.expression();
+            self.consume(TokenType::Semicolon, "Expect ';' after loop condition.".to_string());
+
+            exit_jump = Some(self.emit_jump(OpCode::JumpIfFalse));
+            self.emit_byte(OpCode::Pop);
+        }
+
+        // Increment, which runs after the body so we jump over it and loop back
+        if !self.match_type(TokenType::RightParen)
+        {
+            let body_jump = self.emit_jump(OpCode::Jump);
+            let increment_start = self.current_chunk().code.len();
+
+            self.expression();
+            self.emit_byte(OpCode::Pop);
+            self.consume(TokenType::RightParen, "Expect ')' after for clauses.".to_string());
+
+            self.emit_loop(loop_start);
+            loop_start = increment_start;
+            self.patch_jump(body_jump);
+        }
+
+        self.statement();
+        self.emit_loop(loop_start);
+
+        if let Some(exit_jump) = exit_jump
+        {
+            self.patch_jump(exit_jump);
+            self.emit_byte(OpCode::Pop);
+        }
+
+        self.end_scope();
+    }
+
+    fn print_statement(&mut self)
+    {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after value.".to_string());
+        self.emit_byte(OpCode::Print);
+    }
+
+    fn expression_statement(&mut self)
+    {
+        self.expression();
+        self.consume(TokenType::Semicolon, "Expect ';' after expression.".to_string());
+        self.emit_byte(OpCode::Pop);
+    }
+
+    // Skip tokens until we reach something that looks like a statement boundary
+    fn synchronize(&mut self)
+    {
+        self.panic_mode = false;
+
+        use TokenType::*;
+        while self.current.type_of != EOF
+        {
+            if self.previous.type_of == Semicolon { return; }
+
+            match self.current.type_of
+            {
+                Class | Func | Var | For | If | While | Print | Return => return,
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    fn parse_precedence(&mut self, precedence: Precedence)
+    {
+        self.advance();
+
+        let prefix = match self.get_rule_prefix(self.previous.type_of)
+        {
+            Some(prefix) => prefix,
+            None =>
+            {
+                self.error("Expect expression.".to_string());
+                return;
+            }
+        };
+
+        let can_assign = precedence as usize <= Precedence::Assignment as usize;
+        prefix(self, can_assign);
+
+        while precedence as usize <= self.get_rule_precedence(self.current.type_of) as usize
+        {
+            self.advance();
+
+            if let Some(infix) = self.get_rule_infix(self.previous.type_of)
+            {
+                infix(self, can_assign);
             }
         }
+
+        if can_assign && self.match_type(TokenType::Equal)
+        {
+            self.error("Invalid assignment target.".to_string());
+        }
+    }
+
+    fn lexeme(&self, token: &Token) -> String
+    {
+        self.scanner.lexeme(token).to_string()
+    }
+
+    // Each name is stored once per chunk, however often it's used
+    fn identifier_constant(&mut self, name: &str) -> usize
+    {
+        if let Some(constant) = self.current().names.get(name)
+        {
+            return *constant;
+        }
+
+        let string = self.vm.copy_string(name);
+        let constant = self.add_constant(Value::Obj(string));
+
+        self.current_mut().names.insert(name.to_string(), constant);
+        constant
     }
 
-    fn get_rule(&self, type_of: TokenType) -> &ParseRule<Box<dyn Fn(&mut Parser)>>
+    fn resolve_local(&mut self, compiler: usize, name: &str) -> Option<u8>
     {
-        &self.rules[type_of as usize]
+        let mut found = None;
+
+        for (slot, local) in self.compilers[compiler].locals.iter().enumerate().rev()
+        {
+            if local.name == name
+            {
+                found = Some((slot as u8, local.depth));
+                break;
+            }
+        }
+
+        let (slot, depth) = found?;
+        if depth == -1
+        {
+            self.error("Can't read local variable in its own initializer.".to_string());
+        }
+
+        Some(slot)
     }
 
-    fn get_rule_prefix(&self, type_of: TokenType) -> &Option<Box<dyn Fn(&mut Parser)>>
+    // Looks for the variable in each enclosing function, threading an upvalue
+    // through every function in between
+    fn resolve_upvalue(&mut self, compiler: usize, name: &str) -> Option<u8>
     {
-        &self.get_rule(type_of).prefix
+        if compiler == 0 { return None; }
+
+        if let Some(local) = self.resolve_local(compiler - 1, name)
+        {
+            self.compilers[compiler - 1].locals[local as usize].is_captured = true;
+            return Some(self.add_upvalue(compiler, local, true));
+        }
+
+        if let Some(upvalue) = self.resolve_upvalue(compiler - 1, name)
+        {
+            return Some(self.add_upvalue(compiler, upvalue, false));
+        }
+
+        None
     }
 
-    fn get_rule_infix(&self, type_of: TokenType) -> &Option<Box<dyn Fn(&mut Parser)>>
+    fn add_upvalue(&mut self, compiler: usize, index: u8, is_local: bool) -> u8
     {
-        &self.get_rule(type_of).infix
+        let upvalues = &self.compilers[compiler].upvalues;
+
+        for (i, upvalue) in upvalues.iter().enumerate()
+        {
+            if upvalue.index == index && upvalue.is_local == is_local
+            {
+                return i as u8;
+            }
+        }
+
+        if upvalues.len() == LOCALS_MAX
+        {
+            self.error("Too many closure variables in function.".to_string());
+            return 0;
+        }
+
+        self.compilers[compiler].upvalues.push(Upvalue { index, is_local });
+        (self.compilers[compiler].upvalues.len() - 1) as u8
+    }
+
+    fn add_local(&mut self, name: String)
+    {
+        if self.current().locals.len() == LOCALS_MAX
+        {
+            self.error("Too many local variables in function.".to_string());
+            return;
+        }
+
+        self.current_mut().locals.push(Local { name, depth: -1, is_captured: false });
+    }
+
+    fn declare_variable(&mut self)
+    {
+        // Globals are late bound, so they are not tracked by the compiler
+        if self.current().scope_depth == 0 { return; }
+
+        let name = self.lexeme(&self.previous);
+        let scope_depth = self.current().scope_depth;
+
+        let mut redeclared = false;
+        for local in self.current().locals.iter().rev()
+        {
+            if local.depth != -1 && local.depth < scope_depth { break; }
+
+            if local.name == name
+            {
+                redeclared = true;
+                break;
+            }
+        }
+
+        if redeclared
+        {
+            self.error("Already a variable with this name in this scope.".to_string());
+        }
+
+        self.add_local(name);
+    }
+
+    fn parse_variable(&mut self, message: String) -> usize
+    {
+        self.consume(TokenType::Identifier, message);
+
+        self.declare_variable();
+        if self.current().scope_depth > 0 { return 0; }
+
+        let name = self.lexeme(&self.previous);
+        self.identifier_constant(&name)
+    }
+
+    fn mark_initialized(&mut self)
+    {
+        // Top level functions are globals, so there is no local to mark
+        let compiler = self.current_mut();
+        if compiler.scope_depth == 0 { return; }
+
+        if let Some(local) = compiler.locals.last_mut()
+        {
+            local.depth = compiler.scope_depth;
+        }
+    }
+
+    fn define_variable(&mut self, global: usize)
+    {
+        if self.current().scope_depth > 0
+        {
+            self.mark_initialized();
+            return;
+        }
+
+        self.emit_indexed(OpCode::DefineGlobal, global);
+    }
+
+    fn get_rule(&self, type_of: TokenType) -> ParseRule<'a>
+    {
+        self.rules[type_of as usize]
+    }
+
+    fn get_rule_prefix(&self, type_of: TokenType) -> Option<ParseFn<'a>>
+    {
+        self.get_rule(type_of).prefix
+    }
+
+    fn get_rule_infix(&self, type_of: TokenType) -> Option<ParseFn<'a>>
+    {
+        self.get_rule(type_of).infix
     }
 
     fn get_rule_precedence(&self, type_of: TokenType) -> Precedence
@@ -223,32 +967,208 @@ impl Parser
         rule.precedence
     }
 
-    fn grouping(&mut self)
+    fn grouping(&mut self, _can_assign: bool)
     {
         self.expression();
         self.consume(TokenType::RightParen, "Expect ')' after expression.".to_string());
     }
 
-    fn number(&mut self)
+    fn number(&mut self, _can_assign: bool)
     {
         let tok = &self.previous;
-        let value: Value = self.scanner.substr(tok.start, tok.start + tok.length)
+        let value: f64 = self.scanner.lexeme(tok)
                             .parse()
                             .unwrap();
-        self.emit_constant(value);
+        self.emit_constant(Value::Number(value));
+    }
+
+    fn string(&mut self, _can_assign: bool)
+    {
+        let tok = &self.previous;
+
+        // Trim the surrounding quotes, the scanner has already checked the escapes
+        let lexeme = self.scanner.lexeme(tok);
+        let chars = scanner::unescape(&lexeme[1..lexeme.len() - 1]).unwrap_or_default();
+        let string = self.vm.copy_string(&chars);
+        self.emit_constant(Value::Obj(string));
+    }
+
+    fn variable(&mut self, can_assign: bool)
+    {
+        let name = self.lexeme(&self.previous);
+        self.named_variable(&name, can_assign);
+    }
+
+    fn named_variable(&mut self, name: &str, can_assign: bool)
+    {
+        let compiler = self.compilers.len() - 1;
+
+        let (get_op, set_op, arg) = if let Some(slot) = self.resolve_local(compiler, name)
+        {
+            (OpCode::GetLocal, OpCode::SetLocal, slot as usize)
+        }
+        else if let Some(upvalue) = self.resolve_upvalue(compiler, name)
+        {
+            (OpCode::GetUpvalue, OpCode::SetUpvalue, upvalue as usize)
+        }
+        else
+        {
+            (OpCode::GetGlobal, OpCode::SetGlobal, self.identifier_constant(name))
+        };
+
+        if can_assign && self.match_type(TokenType::Equal)
+        {
+            self.expression();
+            self.emit_indexed(set_op, arg);
+        }
+        else
+        {
+            self.emit_indexed(get_op, arg);
+        }
+    }
+
+    fn call(&mut self, _can_assign: bool)
+    {
+        let arg_count = self.argument_list();
+        self.emit_with_operand(OpCode::Call, arg_count);
+    }
+
+    fn argument_list(&mut self) -> u8
+    {
+        let mut arg_count: usize = 0;
+
+        if !self.check(TokenType::RightParen)
+        {
+            loop
+            {
+                self.expression();
+
+                if arg_count == u8::MAX as usize
+                {
+                    self.error("Can't have more than 255 arguments.".to_string());
+                }
+                arg_count += 1;
+
+                if !self.match_type(TokenType::Comma) { break; }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.".to_string());
+        arg_count as u8
+    }
+
+    fn dot(&mut self, can_assign: bool)
+    {
+        self.consume(TokenType::Identifier, "Expect property name after '.'.".to_string());
+        let name = self.lexeme(&self.previous);
+        let name = self.identifier_constant(&name);
+
+        if can_assign && self.match_type(TokenType::Equal)
+        {
+            self.expression();
+            self.emit_indexed(OpCode::SetProperty, name);
+        }
+        else if self.match_type(TokenType::LeftParen)
+        {
+            let arg_count = self.argument_list();
+            self.emit_indexed(OpCode::Invoke, name);
+            self.emit_operand(arg_count);
+        }
+        else
+        {
+            self.emit_indexed(OpCode::GetProperty, name);
+        }
+    }
+
+    fn this(&mut self, _can_assign: bool)
+    {
+        if self.class_compilers.is_empty()
+        {
+            self.error("Can't use 'this' outside of a class.".to_string());
+            return;
+        }
+
+        self.variable(false);
+    }
+
+    fn super_(&mut self, _can_assign: bool)
+    {
+        match self.class_compilers.last()
+        {
+            None => self.error("Can't use 'parent' outside of a class.".to_string()),
+            Some(class) if !class.has_superclass => self.error("Can't use 'parent' in a class with no superclass.".to_string()),
+            _ => {}
+        }
+
+        self.consume(TokenType::Dot, "Expect '.' after 'parent'.".to_string());
+        self.consume(TokenType::Identifier, "Expect superclass method name.".to_string());
+        let name = self.lexeme(&self.previous);
+        let name = self.identifier_constant(&name);
+
+        self.named_variable("this", false);
+
+        if self.match_type(TokenType::LeftParen)
+        {
+            let arg_count = self.argument_list();
+            self.named_variable("parent", false);
+            self.emit_indexed(OpCode::SuperInvoke, name);
+            self.emit_operand(arg_count);
+        }
+        else
+        {
+            self.named_variable("parent", false);
+            self.emit_indexed(OpCode::GetSuper, name);
+        }
+    }
+
+    fn and(&mut self, _can_assign: bool)
+    {
+        // If the left side is falsey, skip the right side and leave it as the result
+        let end_jump = self.emit_jump(OpCode::JumpIfFalse);
+
+        self.emit_byte(OpCode::Pop);
+        self.parse_precedence(Precedence::And);
+
+        self.patch_jump(end_jump);
     }
 
-    fn unary(&mut self)
+    fn or(&mut self, _can_assign: bool)
     {
-        let operator_type: TokenType = self.previous.type_of;
+        // If the left side is truthy, jump over the right side
+        let else_jump = self.emit_jump(OpCode::JumpIfFalse);
+        let end_jump = self.emit_jump(OpCode::Jump);
+
+        self.patch_jump(else_jump);
+        self.emit_byte(OpCode::Pop);
+
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
+    fn literal(&mut self, _can_assign: bool)
+    {
+        use TokenType::*;
+        match self.previous.type_of
+        {
+            False => self.emit_byte(OpCode::False),
+            Null => self.emit_byte(OpCode::Nil),
+            True => self.emit_byte(OpCode::True),
+            _ => unimplemented!(), // Unreachable
+        }
+    }
+
+    fn unary(&mut self, _can_assign: bool)
+    {
+        let operator = self.previous;
 
         // Compile the operand
         self.parse_precedence(Precedence::Unary);
 
         use TokenType::*;
-        match operator_type
+        match operator.type_of
         {
-            Minus => self.emit_byte(OpCode::Negate),
+            Bang => self.emit_byte_at(OpCode::Not, operator),
+            Minus => self.emit_byte_at(OpCode::Negate, operator),
             _ => unimplemented!(), // Unreachable
         }
     }
@@ -264,39 +1184,135 @@ impl Parser
         self.error_at_current(message);
     }
 
+    fn check(&self, type_of: TokenType) -> bool
+    {
+        self.current.type_of == type_of
+    }
+
+    fn match_type(&mut self, type_of: TokenType) -> bool
+    {
+        if !self.check(type_of) { return false; }
+
+        self.advance();
+        true
+    }
+
     fn emit_return(&mut self)
     {
+        // Initializers always hand back the instance
+        if self.current().type_of == FunctionType::Initializer
+        {
+            self.emit_with_operand(OpCode::GetLocal, 0);
+        }
+        else
+        {
+            self.emit_byte(OpCode::Nil);
+        }
+
         self.emit_byte(OpCode::Return);
     }
 
     fn emit_byte(&mut self, byte: OpCode)
     {
-        self.chunk.write(byte, self.previous.line);
+        self.emit_byte_at(byte, self.previous);
+    }
+
+    // Attributes the instruction to a token other than the last one, ie. an operator
+    fn emit_byte_at(&mut self, byte: OpCode, token: Token)
+    {
+        self.current_chunk().write(byte, token.line, token.column);
+    }
+
+    fn emit_operand(&mut self, operand: u8)
+    {
+        let token = self.previous;
+        self.current_chunk().write_constant(operand as usize, token.line, token.column);
     }
 
-    fn emit_bytes(&mut self, byte1: OpCode, byte2: OpCode)
+    fn emit_with_operand(&mut self, byte: OpCode, operand: u8)
     {
-        self.emit_byte(byte1);
-        self.emit_byte(byte2);
+        self.emit_byte(byte);
+        self.emit_operand(operand);
+    }
+
+    // Emits a jump with a placeholder offset, returning where the offset lives
+    fn emit_jump(&mut self, instruction: OpCode) -> usize
+    {
+        self.emit_byte(instruction);
+        self.emit_operand(0xff);
+        self.emit_operand(0xff);
+
+        self.current_chunk().code.len() - 2
+    }
+
+    fn patch_jump(&mut self, offset: usize)
+    {
+        // -2 to adjust for the bytecode of the jump offset itself
+        let jump = self.current_chunk().code.len() - offset - 2;
+
+        if jump > u16::MAX as usize
+        {
+            self.error("Too much code to jump over.".to_string());
+        }
+
+        self.current_chunk().code[offset] = ((jump >> 8) & 0xff) as u8;
+        self.current_chunk().code[offset + 1] = (jump & 0xff) as u8;
+    }
+
+    fn emit_loop(&mut self, loop_start: usize)
+    {
+        self.emit_byte(OpCode::Loop);
+
+        // +2 to also jump back over the operand of this instruction
+        let offset = self.current_chunk().code.len() - loop_start + 2;
+        if offset > u16::MAX as usize
+        {
+            self.error("Loop body too large.".to_string());
+        }
+
+        self.emit_operand(((offset >> 8) & 0xff) as u8);
+        self.emit_operand((offset & 0xff) as u8);
     }
 
     fn emit_constant(&mut self, value: Value)
     {
-        let constant = self.make_constant(value);
-        self.emit_bytes(OpCode::Constant, OpCode::from(constant));
+        let constant = self.add_constant(value);
+        self.emit_indexed(OpCode::Constant, constant);
     }
 
-    fn make_constant(&mut self, value: Value) -> u8
+    // Constants past the first 256 need the instruction's long form
+    fn emit_indexed(&mut self, byte: OpCode, index: usize)
     {
-        let constant: usize = self.chunk.add_constant(value);
+        if index <= u8::MAX as usize
+        {
+            self.emit_with_operand(byte, index as u8);
+        }
+        else
+        {
+            self.emit_byte(byte.long_form());
+            self.emit_operand((index >> 16) as u8);
+            self.emit_operand((index >> 8) as u8);
+            self.emit_operand(index as u8);
+        }
+    }
 
-        if constant as u8 > u8::MAX
+    fn add_constant(&mut self, value: Value) -> usize
+    {
+        // Chunks being compiled are not reachable by the VM, so keep their objects alive
+        if let Value::Obj(object) = value
+        {
+            self.vm.compiler_roots.push(object);
+        }
+
+        let constant = self.current_chunk().add_constant(value);
+
+        if constant > CONSTANTS_MAX
         {
             self.error("Too many constants in one chunk.".to_string());
             return 0;
         }
 
-        constant as u8
+        constant
     }
 
     fn error_at_current(&mut self, message: String)
@@ -314,22 +1330,28 @@ impl Parser
         if self.panic_mode { return; }
         self.panic_mode = true;
 
-        print!("[line {}] Error", token.line);
-
-        if token.type_of == TokenType::EOF
-        {
-            print!(" at end");
-        }
-        else if token.type_of == TokenType::Error
+        let lexeme = match token.type_of
         {
-            // Nothing
-        }
-        else
+            TokenType::EOF => String::new(),
+            _ => self.lexeme(&token),
+        };
+
+        let incomplete = match token.type_of
         {
-            print!(" at {}", self.scanner.substr(token.start, token.start + token.length));
-        }
+            TokenType::EOF => true,
+            TokenType::Error => self.scanner.error_is_incomplete(),
+            _ => false,
+        };
 
-        println!(": {}", message);
-        self.had_error = false;
+        self.errors.push(CompileError
+        {
+            line: token.line,
+            column: token.column,
+            span: token.span.start..token.span.end,
+            lexeme,
+            message,
+            incomplete,
+        });
+        self.had_error = true;
     }
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 use super::{
-    value,
+    value::{self, Value},
     chunk::{Chunk, OpCode},
 };
 
@@ -15,49 +15,176 @@ pub fn disassemble(chunk: &Chunk, title: String)
     }
 }
 
+// The chunk, then every function compiled into it, nested ones included
+pub fn disassemble_all(chunk: &Chunk, title: String)
+{
+    disassemble(chunk, title);
+
+    for value in &chunk.constants.values
+    {
+        if let Value::Obj(object) = value
+        {
+            if object.is_function()
+            {
+                let function = object.as_function();
+                disassemble_all(&function.chunk, format!("{}", function));
+            }
+        }
+    }
+}
+
 pub fn disassemble_instruction(chunk: &Chunk, offset: usize) -> usize
 {
     print!("{:04} ", offset);
 
-    if offset > 0 && chunk.lines[offset] == chunk.lines[offset - 1]
+    let line = chunk.get_line(offset);
+    let column = chunk.get_column(offset);
+
+    if offset > 0 && line == chunk.get_line(offset - 1) && column == chunk.get_column(offset - 1)
     {
-        print!("     | ");
+        print!("        | ");
     }
     else
     {
-        print!("{:04} ", chunk.lines[offset]);
+        print!("{:4}:{:<4} ", line, column);
     }
 
-    let instruction = OpCode::from(chunk.code[offset]);
+    let (instruction, long) = OpCode::from(chunk.code[offset]).short_form();
     
     use OpCode::*;
     match instruction
     {
-        Constant => return constant_instruction("OP_CONSTANT".to_string(), &chunk, offset),
-        Negate => return simple_instruction("OP_NEGATE".to_string(), offset),
-        Add => return simple_instruction("OP_ADD".to_string(), offset),
-        Subtract => return simple_instruction("OP_SUBTRACT".to_string(), offset),
-        Multiply => return simple_instruction("OP_MULTIPLY".to_string(), offset),
-        Divide => return simple_instruction("OP_DIVIDE".to_string(), offset),
-        Return => return simple_instruction("OP_RETURN".to_string(), offset),
-       
+        Constant => constant_instruction("OP_CONSTANT".to_string(), chunk, offset, long),
+        Nil => simple_instruction("OP_NIL".to_string(), offset),
+        True => simple_instruction("OP_TRUE".to_string(), offset),
+        False => simple_instruction("OP_FALSE".to_string(), offset),
+        Equal => simple_instruction("OP_EQUAL".to_string(), offset),
+        Greater => simple_instruction("OP_GREATER".to_string(), offset),
+        GreaterEqual => simple_instruction("OP_GREATER_EQUAL".to_string(), offset),
+        Less => simple_instruction("OP_LESS".to_string(), offset),
+        LessEqual => simple_instruction("OP_LESS_EQUAL".to_string(), offset),
+        Not => simple_instruction("OP_NOT".to_string(), offset),
+        Negate => simple_instruction("OP_NEGATE".to_string(), offset),
+        Add => simple_instruction("OP_ADD".to_string(), offset),
+        Subtract => simple_instruction("OP_SUBTRACT".to_string(), offset),
+        Multiply => simple_instruction("OP_MULTIPLY".to_string(), offset),
+        Divide => simple_instruction("OP_DIVIDE".to_string(), offset),
+        Pop => simple_instruction("OP_POP".to_string(), offset),
+        GetLocal => byte_instruction("OP_GET_LOCAL".to_string(), chunk, offset),
+        SetLocal => byte_instruction("OP_SET_LOCAL".to_string(), chunk, offset),
+        GetGlobal => constant_instruction("OP_GET_GLOBAL".to_string(), chunk, offset, long),
+        DefineGlobal => constant_instruction("OP_DEFINE_GLOBAL".to_string(), chunk, offset, long),
+        SetGlobal => constant_instruction("OP_SET_GLOBAL".to_string(), chunk, offset, long),
+        GetUpvalue => byte_instruction("OP_GET_UPVALUE".to_string(), chunk, offset),
+        SetUpvalue => byte_instruction("OP_SET_UPVALUE".to_string(), chunk, offset),
+        GetProperty => constant_instruction("OP_GET_PROPERTY".to_string(), chunk, offset, long),
+        SetProperty => constant_instruction("OP_SET_PROPERTY".to_string(), chunk, offset, long),
+        GetSuper => constant_instruction("OP_GET_SUPER".to_string(), chunk, offset, long),
+        Print => simple_instruction("OP_PRINT".to_string(), offset),
+        Jump => jump_instruction("OP_JUMP".to_string(), 1, chunk, offset),
+        JumpIfFalse => jump_instruction("OP_JUMP_IF_FALSE".to_string(), 1, chunk, offset),
+        Loop => jump_instruction("OP_LOOP".to_string(), -1, chunk, offset),
+        Call => byte_instruction("OP_CALL".to_string(), chunk, offset),
+        Invoke => invoke_instruction("OP_INVOKE".to_string(), chunk, offset, long),
+        SuperInvoke => invoke_instruction("OP_SUPER_INVOKE".to_string(), chunk, offset, long),
+        Closure => closure_instruction("OP_CLOSURE".to_string(), chunk, offset, long),
+        CloseUpvalue => simple_instruction("OP_CLOSE_UPVALUE".to_string(), offset),
+        Return => simple_instruction("OP_RETURN".to_string(), offset),
+        Class => constant_instruction("OP_CLASS".to_string(), chunk, offset, long),
+        Inherit => simple_instruction("OP_INHERIT".to_string(), offset),
+        Method => constant_instruction("OP_METHOD".to_string(), chunk, offset, long),
+
         _ => {
             println!("Unknown opcode: {:?}", instruction);
-            return  offset + 1
+            offset + 1
         }
     }
 }
 
-fn constant_instruction(name: String, chunk: &Chunk, offset: usize) -> usize
+// Long forms have a 24-bit constant index, returns it and the offset past it
+fn read_index(chunk: &Chunk, offset: usize, long: bool) -> (usize, usize)
+{
+    if !long
+    {
+        return (chunk.code[offset + 1] as usize, offset + 2);
+    }
+
+    let index = ((chunk.code[offset + 1] as usize) << 16)
+        | ((chunk.code[offset + 2] as usize) << 8)
+        | chunk.code[offset + 3] as usize;
+
+    (index, offset + 4)
+}
+
+fn long_name(name: String, long: bool) -> String
 {
-    let constant = chunk.code[offset + 1];
-    print!("{:16} {:04} '", name, constant);
-    value::print_value(chunk.constants.values[constant as usize]);
+    if long { name + "_LONG" } else { name }
+}
+
+fn constant_instruction(name: String, chunk: &Chunk, offset: usize, long: bool) -> usize
+{
+    let (constant, offset) = read_index(chunk, offset, long);
+    print!("{:16} {:04} '", long_name(name, long), constant);
+    value::print_value(chunk.constants.values[constant]);
     println!("'");
 
+    offset
+}
+
+fn invoke_instruction(name: String, chunk: &Chunk, offset: usize, long: bool) -> usize
+{
+    let (constant, offset) = read_index(chunk, offset, long);
+    let arg_count = chunk.code[offset];
+    print!("{:16} ({} args) {:04} '", long_name(name, long), arg_count, constant);
+    value::print_value(chunk.constants.values[constant]);
+    println!("'");
+
+    offset + 1
+}
+
+fn closure_instruction(name: String, chunk: &Chunk, offset: usize, long: bool) -> usize
+{
+    let (constant, mut offset) = read_index(chunk, offset, long);
+
+    let function = chunk.constants.values[constant];
+    print!("{:16} {:04} ", long_name(name, long), constant);
+    value::print_value(function);
+    println!();
+
+    // Each captured variable is encoded as an (is_local, index) pair
+    if let Some(function) = function.as_obj()
+    {
+        for _ in 0..function.as_function().upvalue_count
+        {
+            let is_local = chunk.code[offset];
+            let index = chunk.code[offset + 1];
+
+            println!("{:04}      |                     {} {}",
+                offset, if is_local == 1 { "local" } else { "upvalue" }, index);
+            offset += 2;
+        }
+    }
+
+    offset
+}
+
+fn byte_instruction(name: String, chunk: &Chunk, offset: usize) -> usize
+{
+    let slot = chunk.code[offset + 1];
+    println!("{:16} {:04}", name, slot);
+
     offset + 2
 }
 
+fn jump_instruction(name: String, sign: i64, chunk: &Chunk, offset: usize) -> usize
+{
+    let jump = ((chunk.code[offset + 1] as u16) << 8) | chunk.code[offset + 2] as u16;
+    let target = offset as i64 + 3 + sign * jump as i64;
+    println!("{:16} {:04} -> {}", name, offset, target);
+
+    offset + 3
+}
+
 fn simple_instruction(name: String, offset: usize) -> usize
 {
     println!("{}", name);
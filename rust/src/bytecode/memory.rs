@@ -0,0 +1,220 @@
+use std::mem;
+
+use super::{
+    vm::VM,
+    chunk::LineRun,
+    object::{Obj, ObjRef, ObjType, UpvalueLocation},
+    value::Value,
+};
+
+const GC_HEAP_GROW_FACTOR: usize = 2;
+pub(super) const GC_INITIAL_THRESHOLD: usize = 1024 * 1024;
+
+impl ObjType
+{
+    // A rough count of the bytes a new object holds on to, used to pace the collector
+    pub(super) fn size(&self) -> usize
+    {
+        let owned = match self
+        {
+            ObjType::String(string) => string.chars.capacity(),
+            ObjType::Function(function) =>
+            {
+                let chunk = &function.chunk;
+                chunk.code.capacity()
+                    + chunk.lines.capacity() * mem::size_of::<LineRun>()
+                    + chunk.constants.values.capacity() * mem::size_of::<Value>()
+            }
+            ObjType::Closure(closure) => closure.upvalues.capacity() * mem::size_of::<ObjRef>(),
+            ObjType::Class(class) => class.methods.borrow().capacity() * mem::size_of::<(ObjRef, Value)>(),
+            ObjType::Instance(instance) => instance.fields.borrow().capacity() * mem::size_of::<(ObjRef, Value)>(),
+            ObjType::Native(_) | ObjType::Upvalue(_) | ObjType::BoundMethod(_) => 0,
+        };
+
+        mem::size_of::<Obj>() + owned
+    }
+}
+
+impl VM
+{
+    // Called on every allocation, the new object is treated as a root for this
+    // collection since the caller has not had a chance to store it anywhere yet
+    pub(super) fn track_allocation(&mut self, object: ObjRef)
+    {
+        self.bytes_allocated += object.size;
+
+        if cfg!(stress_gc = "true") || self.bytes_allocated > self.next_gc
+        {
+            self.push(Value::Obj(object));
+            self.collect_garbage();
+            self.pop();
+        }
+    }
+
+    pub fn collect_garbage(&mut self)
+    {
+        self.mark_roots();
+        self.trace_references();
+
+        // The intern table holds its strings weakly
+        self.strings.retain(|_, string| string.is_marked.get());
+
+        self.sweep();
+
+        self.next_gc = (self.bytes_allocated * GC_HEAP_GROW_FACTOR).max(GC_INITIAL_THRESHOLD);
+    }
+
+    fn mark_roots(&mut self)
+    {
+        for i in 0..self.stack.len()
+        {
+            self.mark_value(self.stack[i]);
+        }
+
+        for i in 0..self.frames.len()
+        {
+            self.mark_object(self.frames[i].closure);
+        }
+
+        let globals: Vec<(ObjRef, Value)> = self.globals.iter().map(|(name, value)| (*name, *value)).collect();
+        for (name, value) in globals
+        {
+            self.mark_object(name);
+            self.mark_value(value);
+        }
+
+        for i in 0..self.open_upvalues.len()
+        {
+            self.mark_object(self.open_upvalues[i]);
+        }
+
+        for i in 0..self.compiler_roots.len()
+        {
+            self.mark_object(self.compiler_roots[i]);
+        }
+
+        if let Some(init_string) = self.init_string
+        {
+            self.mark_object(init_string);
+        }
+    }
+
+    fn mark_value(&mut self, value: Value)
+    {
+        if let Value::Obj(object) = value
+        {
+            self.mark_object(object);
+        }
+    }
+
+    fn mark_object(&mut self, object: ObjRef)
+    {
+        if object.is_marked.get() { return; }
+
+        object.is_marked.set(true);
+        self.gray_stack.push(object);
+    }
+
+    fn trace_references(&mut self)
+    {
+        while let Some(object) = self.gray_stack.pop()
+        {
+            self.blacken_object(object);
+        }
+    }
+
+    // Marks everything the object references, turning it black
+    fn blacken_object(&mut self, object: ObjRef)
+    {
+        match &object.type_of
+        {
+            ObjType::String(_) => {}
+            ObjType::Native(native) => self.mark_object(native.name),
+            ObjType::Function(function) =>
+            {
+                if let Some(name) = function.name
+                {
+                    self.mark_object(name);
+                }
+
+                for constant in &function.chunk.constants.values
+                {
+                    self.mark_value(*constant);
+                }
+            }
+            ObjType::Closure(closure) =>
+            {
+                self.mark_object(closure.function);
+
+                for upvalue in &closure.upvalues
+                {
+                    self.mark_object(*upvalue);
+                }
+            }
+            ObjType::Upvalue(upvalue) =>
+            {
+                if let UpvalueLocation::Closed(value) = upvalue.location.get()
+                {
+                    self.mark_value(value);
+                }
+            }
+            ObjType::Class(class) =>
+            {
+                self.mark_object(class.name);
+
+                for (name, method) in class.methods.borrow().iter()
+                {
+                    self.mark_object(*name);
+                    self.mark_value(*method);
+                }
+            }
+            ObjType::Instance(instance) =>
+            {
+                self.mark_object(instance.class);
+
+                for (name, value) in instance.fields.borrow().iter()
+                {
+                    self.mark_object(*name);
+                    self.mark_value(*value);
+                }
+            }
+            ObjType::BoundMethod(bound) =>
+            {
+                self.mark_value(bound.receiver);
+                self.mark_object(bound.method);
+            }
+        }
+    }
+
+    fn sweep(&mut self)
+    {
+        let mut freed = 0;
+
+        self.objects.retain(|object|
+        {
+            if object.is_marked.get()
+            {
+                object.is_marked.set(false);
+                return true;
+            }
+
+            freed += object.size;
+            object.free();
+            false
+        });
+
+        self.bytes_allocated -= freed;
+    }
+
+    pub(super) fn free_objects(&mut self)
+    {
+        self.strings.clear();
+
+        for object in self.objects.drain(..)
+        {
+            object.free();
+        }
+
+        self.bytes_allocated = 0;
+    }
+}
@@ -1,6 +1,9 @@
 pub mod chunk;
 pub mod debug;
 pub mod value;
+pub mod object;
+pub mod memory;
+pub mod native;
 pub mod vm;
 pub mod compiler;
-pub mod scanner;
\ No newline at end of file
+pub mod serialize;
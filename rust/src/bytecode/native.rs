@@ -0,0 +1,38 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    vm::VM,
+    value::Value,
+};
+
+// Seconds since the unix epoch, mostly useful for timing scripts
+pub fn clock(_vm: &mut VM, _args: &[Value]) -> Result<Value, String>
+{
+    match SystemTime::now().duration_since(UNIX_EPOCH)
+    {
+        Ok(time) => Ok(Value::Number(time.as_secs_f64())),
+        Err(_) => Err("System clock is set before the unix epoch.".to_string()),
+    }
+}
+
+pub fn argc(vm: &mut VM, _args: &[Value]) -> Result<Value, String>
+{
+    Ok(Value::Number(vm.args.len() as f64))
+}
+
+pub fn argv(vm: &mut VM, args: &[Value]) -> Result<Value, String>
+{
+    let index = match args[0]
+    {
+        Value::Number(index) => index,
+        _ => return Err("Argument index must be a number.".to_string()),
+    };
+
+    let arg = match vm.args.get(index as usize)
+    {
+        Some(arg) if index.fract() == 0.0 && index >= 0.0 => arg.clone(),
+        _ => return Err("Argument index out of range.".to_string()),
+    };
+
+    Ok(Value::Obj(vm.take_string(arg)))
+}
@@ -0,0 +1,384 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+    ptr::NonNull,
+};
+
+use super::{
+    vm::VM,
+    chunk::Chunk,
+    value::Value,
+};
+
+pub enum ObjType
+{
+    String(ObjString),
+    Function(ObjFunction),
+    Native(ObjNative),
+    Closure(ObjClosure),
+    Upvalue(ObjUpvalue),
+    Class(ObjClass),
+    Instance(ObjInstance),
+    BoundMethod(ObjBoundMethod),
+}
+
+pub struct Obj
+{
+    pub(super) is_marked: Cell<bool>,
+    // Bytes counted when it was allocated, so freeing it gives back the same amount
+    pub(super) size: usize,
+    pub type_of: ObjType,
+}
+
+pub struct ObjString
+{
+    pub chars: String,
+}
+
+pub struct ObjFunction
+{
+    pub arity: usize,
+    pub upvalue_count: usize,
+    pub chunk: Chunk,
+    // The top-level script has no name
+    pub name: Option<ObjRef>,
+}
+
+// Natives get the VM so they can allocate, and may fail with a runtime error message
+pub type NativeFn = fn(&mut VM, &[Value]) -> Result<Value, String>;
+
+pub struct ObjNative
+{
+    pub name: ObjRef,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+pub struct ObjClosure
+{
+    pub function: ObjRef,
+    pub upvalues: Vec<ObjRef>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum UpvalueLocation
+{
+    // Still alive on the VM stack at this slot
+    Stack(usize),
+    // Hoisted off the stack once the variable went out of scope
+    Closed(Value),
+}
+
+pub struct ObjUpvalue
+{
+    pub location: Cell<UpvalueLocation>,
+}
+
+pub struct ObjClass
+{
+    pub name: ObjRef,
+    pub methods: RefCell<HashMap<ObjRef, Value>>,
+}
+
+pub struct ObjInstance
+{
+    pub class: ObjRef,
+    pub fields: RefCell<HashMap<ObjRef, Value>>,
+}
+
+// A method closure paired with the instance it was accessed from
+pub struct ObjBoundMethod
+{
+    pub receiver: Value,
+    pub method: ObjRef,
+}
+
+impl ObjFunction
+{
+    pub fn new() -> ObjFunction
+    {
+        ObjFunction
+        {
+            arity: 0,
+            upvalue_count: 0,
+            chunk: Chunk::new(),
+            name: None,
+        }
+    }
+}
+
+impl Default for ObjFunction
+{
+    fn default() -> Self
+    {
+        ObjFunction::new()
+    }
+}
+
+// A handle to an object living on the VM heap. The VM owns every object
+// it hands one of these out for, so they stay valid until the VM frees them.
+#[derive(Copy, Clone)]
+pub struct ObjRef(NonNull<Obj>);
+
+impl ObjRef
+{
+    fn new(obj: Obj) -> ObjRef
+    {
+        ObjRef(NonNull::from(Box::leak(Box::new(obj))))
+    }
+
+    // Only the VM should be freeing objects, and only once nothing can reach them
+    pub(super) fn free(self)
+    {
+        unsafe { drop(Box::from_raw(self.0.as_ptr())); }
+    }
+}
+
+impl Deref for ObjRef
+{
+    type Target = Obj;
+
+    fn deref(&self) -> &Obj
+    {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+// Strings are interned, so comparing handles is enough for equality
+impl PartialEq for ObjRef
+{
+    fn eq(&self, other: &ObjRef) -> bool
+    {
+        self.0 == other.0
+    }
+}
+
+impl Eq for ObjRef {}
+
+impl Hash for ObjRef
+{
+    fn hash<H: Hasher>(&self, state: &mut H)
+    {
+        self.0.hash(state);
+    }
+}
+
+impl fmt::Debug for ObjRef
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", **self)
+    }
+}
+
+impl fmt::Display for ObjRef
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", **self)
+    }
+}
+
+impl Obj
+{
+    pub fn is_string(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::String(_))
+    }
+
+    pub fn as_string(&self) -> &ObjString
+    {
+        match &self.type_of
+        {
+            ObjType::String(string) => string,
+            _ => panic!("Object is not a string."),
+        }
+    }
+
+    pub fn is_function(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::Function(_))
+    }
+
+    pub fn as_function(&self) -> &ObjFunction
+    {
+        match &self.type_of
+        {
+            ObjType::Function(function) => function,
+            _ => panic!("Object is not a function."),
+        }
+    }
+
+    pub fn is_native(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::Native(_))
+    }
+
+    pub fn as_native(&self) -> &ObjNative
+    {
+        match &self.type_of
+        {
+            ObjType::Native(native) => native,
+            _ => panic!("Object is not a native function."),
+        }
+    }
+
+    pub fn is_closure(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::Closure(_))
+    }
+
+    pub fn as_closure(&self) -> &ObjClosure
+    {
+        match &self.type_of
+        {
+            ObjType::Closure(closure) => closure,
+            _ => panic!("Object is not a closure."),
+        }
+    }
+
+    pub fn as_upvalue(&self) -> &ObjUpvalue
+    {
+        match &self.type_of
+        {
+            ObjType::Upvalue(upvalue) => upvalue,
+            _ => panic!("Object is not an upvalue."),
+        }
+    }
+
+    pub fn is_class(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::Class(_))
+    }
+
+    pub fn as_class(&self) -> &ObjClass
+    {
+        match &self.type_of
+        {
+            ObjType::Class(class) => class,
+            _ => panic!("Object is not a class."),
+        }
+    }
+
+    pub fn is_instance(&self) -> bool
+    {
+        matches!(self.type_of, ObjType::Instance(_))
+    }
+
+    pub fn as_instance(&self) -> &ObjInstance
+    {
+        match &self.type_of
+        {
+            ObjType::Instance(instance) => instance,
+            _ => panic!("Object is not an instance."),
+        }
+    }
+}
+
+impl fmt::Display for Obj
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match &self.type_of
+        {
+            ObjType::String(string) => write!(f, "{}", string.chars),
+            ObjType::Function(function) => write!(f, "{}", function),
+            ObjType::Native(_) => write!(f, "<native fn>"),
+            ObjType::Closure(closure) => write!(f, "{}", closure.function),
+            ObjType::Upvalue(_) => write!(f, "upvalue"),
+            ObjType::Class(class) => write!(f, "{}", class.name),
+            ObjType::Instance(instance) => write!(f, "{} instance", instance.class.as_class().name),
+            ObjType::BoundMethod(bound) => write!(f, "{}", bound.method),
+        }
+    }
+}
+
+impl fmt::Display for ObjFunction
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self.name
+        {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<script>"),
+        }
+    }
+}
+
+impl VM
+{
+    fn allocate_object(&mut self, type_of: ObjType) -> ObjRef
+    {
+        let size = type_of.size();
+        let object = ObjRef::new(Obj { is_marked: Cell::new(false), size, type_of });
+        self.objects.push(object);
+        self.track_allocation(object);
+        object
+    }
+
+    pub fn new_function(&mut self, function: ObjFunction) -> ObjRef
+    {
+        self.allocate_object(ObjType::Function(function))
+    }
+
+    pub fn new_native(&mut self, name: ObjRef, arity: usize, function: NativeFn) -> ObjRef
+    {
+        self.allocate_object(ObjType::Native(ObjNative { name, arity, function }))
+    }
+
+    pub fn new_closure(&mut self, function: ObjRef, upvalues: Vec<ObjRef>) -> ObjRef
+    {
+        self.allocate_object(ObjType::Closure(ObjClosure { function, upvalues }))
+    }
+
+    pub fn new_upvalue(&mut self, slot: usize) -> ObjRef
+    {
+        self.allocate_object(ObjType::Upvalue(ObjUpvalue { location: Cell::new(UpvalueLocation::Stack(slot)) }))
+    }
+
+    pub fn new_class(&mut self, name: ObjRef) -> ObjRef
+    {
+        self.allocate_object(ObjType::Class(ObjClass { name, methods: RefCell::new(HashMap::new()) }))
+    }
+
+    pub fn new_instance(&mut self, class: ObjRef) -> ObjRef
+    {
+        self.allocate_object(ObjType::Instance(ObjInstance { class, fields: RefCell::new(HashMap::new()) }))
+    }
+
+    pub fn new_bound_method(&mut self, receiver: Value, method: ObjRef) -> ObjRef
+    {
+        self.allocate_object(ObjType::BoundMethod(ObjBoundMethod { receiver, method }))
+    }
+
+    fn allocate_string(&mut self, chars: String) -> ObjRef
+    {
+        let string = self.allocate_object(ObjType::String(ObjString { chars: chars.clone() }));
+        self.strings.insert(chars, string);
+        string
+    }
+
+    // Takes ownership of an already built string, ie. the result of a concatenation
+    pub fn take_string(&mut self, chars: String) -> ObjRef
+    {
+        if let Some(interned) = self.strings.get(&chars)
+        {
+            return *interned;
+        }
+
+        self.allocate_string(chars)
+    }
+
+    pub fn copy_string(&mut self, chars: &str) -> ObjRef
+    {
+        if let Some(interned) = self.strings.get(chars)
+        {
+            return *interned;
+        }
+
+        self.allocate_string(chars.to_string())
+    }
+}
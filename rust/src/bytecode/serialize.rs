@@ -0,0 +1,582 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Read, Write},
+};
+
+use super::{
+    vm::VM,
+    chunk::{Chunk, LineRun, OpCode},
+    object::{ObjFunction, ObjRef, ObjType},
+    value::Value,
+};
+
+/*
+    Compiled chunk file layout, all integers little endian:
+
+    magic       "RLOX"
+    version     u16
+    chunk:
+        code        u32 length, then the raw bytes
+        lines       u32 run count, then an offset, line and column per run
+        constants   u32 count, then one tagged constant each
+
+    A function constant carries its arity, upvalue count, optional name
+    and its own nested chunk, laid out just like the top-level one.
+*/
+
+pub const MAGIC: &[u8; 4] = b"RLOX";
+pub const VERSION: u16 = 5;
+
+const TAG_NIL: u8 = 0;
+const TAG_BOOL: u8 = 1;
+const TAG_NUMBER: u8 = 2;
+const TAG_STRING: u8 = 3;
+const TAG_FUNCTION: u8 = 4;
+
+#[derive(Debug)]
+pub enum ChunkError
+{
+    Io(io::Error),
+    // The file ended part way through
+    Truncated,
+    BadMagic,
+    UnsupportedVersion(u16),
+    Malformed(String),
+}
+
+impl fmt::Display for ChunkError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        use ChunkError::*;
+        match self
+        {
+            Io(error) => write!(f, "I/O error: {}", error),
+            Truncated => write!(f, "Bytecode file is truncated."),
+            BadMagic => write!(f, "Not a bytecode file."),
+            UnsupportedVersion(version) => write!(f, "Unsupported bytecode version {}, expected {}.", version, VERSION),
+            Malformed(message) => write!(f, "Malformed bytecode: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+impl From<io::Error> for ChunkError
+{
+    fn from(error: io::Error) -> Self
+    {
+        match error.kind()
+        {
+            io::ErrorKind::UnexpectedEof => ChunkError::Truncated,
+            _ => ChunkError::Io(error),
+        }
+    }
+}
+
+impl Chunk
+{
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), ChunkError>
+    {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        self.write_body(writer)
+    }
+
+    // Objects are allocated on the VM heap, so loading needs one to own them
+    pub fn read_from<R: Read>(reader: &mut R, vm: &mut VM) -> Result<Chunk, ChunkError>
+    {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC
+        {
+            return Err(ChunkError::BadMagic);
+        }
+
+        let version = read_u16(reader)?;
+        if version != VERSION
+        {
+            return Err(ChunkError::UnsupportedVersion(version));
+        }
+
+        // Constants stay rooted until the whole chunk is built, like while compiling
+        let roots = vm.compiler_roots.len();
+        let result = Chunk::read_body(reader, vm);
+        vm.compiler_roots.truncate(roots);
+        let chunk = result?;
+
+        // The script's frame starts with just the script itself
+        chunk.validate(1, 0)?;
+
+        let mut rest = [0; 1];
+        if reader.read(&mut rest)? != 0
+        {
+            return Err(ChunkError::Malformed("trailing bytes after chunk.".to_string()));
+        }
+
+        Ok(chunk)
+    }
+
+    fn write_body<W: Write>(&self, writer: &mut W) -> Result<(), ChunkError>
+    {
+        write_u32(writer, self.code.len())?;
+        writer.write_all(&self.code)?;
+
+        write_u32(writer, self.lines.len())?;
+        for run in &self.lines
+        {
+            write_u32(writer, run.offset as usize)?;
+            write_u32(writer, run.line as usize)?;
+            write_u32(writer, run.column as usize)?;
+        }
+
+        write_u32(writer, self.constants.values.len())?;
+        for constant in &self.constants.values
+        {
+            write_constant(writer, *constant)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_body<R: Read>(reader: &mut R, vm: &mut VM) -> Result<Chunk, ChunkError>
+    {
+        let mut chunk = Chunk::new();
+
+        let length = read_u32(reader)?;
+        chunk.code = read_bytes(reader, length)?;
+
+        let runs = read_u32(reader)?;
+        for _ in 0..runs
+        {
+            let offset = read_u32(reader)?;
+            let line = read_u32(reader)?;
+            let column = read_u32(reader)?;
+            chunk.lines.push(LineRun { offset: offset as u32, line: line as u32, column: column as u32 });
+        }
+
+        let count = read_u32(reader)?;
+        for _ in 0..count
+        {
+            let constant = read_constant(reader, vm)?;
+            chunk.add_constant(constant);
+        }
+
+        Ok(chunk)
+    }
+
+    // Walks every instruction so the VM never reads past the code or the
+    // constant pool, never jumps into the middle of an instruction and never
+    // touches a stack slot or upvalue that isn't there. The frame starts with
+    // slots values on the stack, the callee and its parameters
+    fn validate(&self, slots: usize, upvalue_count: usize) -> Result<(), ChunkError>
+    {
+        self.validate_lines()?;
+
+        // Length of the instruction starting at each offset
+        let mut starts = HashMap::new();
+        let mut jumps = Vec::new();
+
+        let mut offset = 0;
+        let mut returns = false;
+        while offset < self.code.len()
+        {
+            let (instruction, long) = OpCode::from(self.code[offset]).short_form();
+            returns = matches!(instruction, OpCode::Return);
+
+            // Bytes taken by the constant index
+            let width = if long { 3 } else { 1 };
+
+            use OpCode::*;
+            let length = match instruction
+            {
+                Constant => { self.check_constant(offset, width, false)?; 1 + width }
+                GetGlobal | DefineGlobal | SetGlobal | GetProperty | SetProperty | GetSuper | Class | Method =>
+                {
+                    self.check_constant(offset, width, true)?;
+                    1 + width
+                }
+                GetLocal | SetLocal | GetUpvalue | SetUpvalue | Call => { self.check_operands(offset, 1)?; 2 }
+                Jump | JumpIfFalse | Loop =>
+                {
+                    self.check_operands(offset, 2)?;
+
+                    let jump = ((self.code[offset + 1] as usize) << 8) | self.code[offset + 2] as usize;
+                    let target = match instruction
+                    {
+                        Loop => (offset + 3).checked_sub(jump),
+                        _ => Some(offset + 3 + jump),
+                    };
+
+                    match target
+                    {
+                        Some(target) if target < self.code.len() => jumps.push((offset, target)),
+                        _ => return Err(malformed(offset, "jump target out of range.")),
+                    }
+                    3
+                }
+                Invoke | SuperInvoke =>
+                {
+                    self.check_constant(offset, width, true)?;
+                    self.check_operands(offset, width + 1)?;
+                    2 + width
+                }
+                Closure =>
+                {
+                    let function = match self.check_constant(offset, width, false)?
+                    {
+                        Value::Obj(object) if object.is_function() => object,
+                        _ => return Err(malformed(offset, "closure constant is not a function.")),
+                    };
+
+                    let length = 1 + width + function.as_function().upvalue_count * 2;
+                    self.check_operands(offset, length - 1)?;
+                    length
+                }
+                Unknown => return Err(malformed(offset, &format!("unknown opcode {}.", self.code[offset]))),
+                _ => 1,
+            };
+
+            starts.insert(offset, length);
+            offset += length;
+        }
+
+        // Otherwise the VM would run straight off the end of the code
+        if !returns
+        {
+            return Err(malformed(self.code.len(), "chunk does not end with a return."));
+        }
+
+        for (offset, target) in jumps
+        {
+            if !starts.contains_key(&target)
+            {
+                return Err(malformed(offset, "jump target is not the start of an instruction."));
+            }
+        }
+
+        self.validate_stack(&starts, slots, upvalue_count)
+    }
+
+    // Follows every path through the code, counting the values the frame has
+    // on the stack. Each instruction must see the same count whichever way
+    // it's reached, like the compiler always leaves it
+    fn validate_stack(&self, starts: &HashMap<usize, usize>, slots: usize, upvalue_count: usize) -> Result<(), ChunkError>
+    {
+        let mut heights = HashMap::new();
+        let mut pending = vec![(0, slots)];
+
+        while let Some((offset, height)) = pending.pop()
+        {
+            match heights.insert(offset, height)
+            {
+                Some(seen) if seen == height => continue,
+                Some(_) => return Err(malformed(offset, "stack height differs between the paths reaching it.")),
+                None => {},
+            }
+
+            let length = match starts.get(&offset)
+            {
+                Some(length) => *length,
+                None => return Err(malformed(offset, "code runs off the end without returning.")),
+            };
+
+            let operand = |i: usize| self.code[offset + i] as usize;
+
+            use OpCode::*;
+            let (instruction, long) = OpCode::from(self.code[offset]).short_form();
+            // Operands after the constant index start here
+            let after = if long { 4 } else { 2 };
+
+            // Values taken off the stack, then put back
+            let (pops, pushes) = match instruction
+            {
+                Constant | Nil | True | False | GetLocal | GetGlobal | GetUpvalue | Closure | Class => (0, 1),
+                Equal | Greater | GreaterEqual | Less | LessEqual | Add | Subtract | Multiply | Divide => (2, 1),
+                Not | Negate | GetProperty => (1, 1),
+                // These only peek at the top
+                SetLocal | SetGlobal | SetUpvalue | JumpIfFalse => (1, 1),
+                Pop | Print | DefineGlobal | CloseUpvalue | Return => (1, 0),
+                SetProperty | GetSuper | Inherit | Method => (2, 1),
+                Call => (operand(1) + 1, 1),
+                Invoke => (operand(after) + 1, 1),
+                // The superclass sits above the receiver and arguments
+                SuperInvoke => (operand(after) + 2, 1),
+                _ => (0, 0),
+            };
+
+            if pops > height
+            {
+                return Err(malformed(offset, "instruction pops more values than the stack holds."));
+            }
+
+            match instruction
+            {
+                GetLocal | SetLocal if operand(1) >= height =>
+                {
+                    return Err(malformed(offset, &format!("local slot {} is out of range.", operand(1))));
+                }
+                GetUpvalue | SetUpvalue if operand(1) >= upvalue_count =>
+                {
+                    return Err(malformed(offset, &format!("upvalue {} is out of range.", operand(1))));
+                }
+                Closure =>
+                {
+                    for pair in (after..length).step_by(2)
+                    {
+                        let (is_local, index) = (operand(pair), operand(pair + 1));
+
+                        let in_range = match is_local
+                        {
+                            1 => index < height,
+                            0 => index < upvalue_count,
+                            _ => return Err(malformed(offset, &format!("invalid capture flag {}.", is_local))),
+                        };
+
+                        if !in_range
+                        {
+                            return Err(malformed(offset, &format!("captured variable {} is out of range.", index)));
+                        }
+                    }
+                }
+                _ => {},
+            }
+
+            let height = height - pops + pushes;
+            let target = |sign: i64| (offset as i64 + 3 + sign * (((operand(1) << 8) | operand(2)) as i64)) as usize;
+
+            match instruction
+            {
+                Return => {},
+                Jump => pending.push((target(1), height)),
+                Loop => pending.push((target(-1), height)),
+                JumpIfFalse =>
+                {
+                    pending.push((target(1), height));
+                    pending.push((offset + length, height));
+                }
+                _ => pending.push((offset + length, height)),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every byte of code needs a position, so runs must start at zero and keep increasing
+    fn validate_lines(&self) -> Result<(), ChunkError>
+    {
+        if !self.code.is_empty() && self.lines.first().map(|run| run.offset) != Some(0)
+        {
+            return Err(ChunkError::Malformed("line table does not start at offset 0.".to_string()));
+        }
+
+        for pair in self.lines.windows(2)
+        {
+            if pair[1].offset <= pair[0].offset
+            {
+                return Err(ChunkError::Malformed("line table offsets are not increasing.".to_string()));
+            }
+        }
+
+        if let Some(last) = self.lines.last()
+        {
+            if last.offset as usize >= self.code.len()
+            {
+                return Err(ChunkError::Malformed("line table runs past the end of the code.".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_operands(&self, offset: usize, count: usize) -> Result<(), ChunkError>
+    {
+        if offset + count >= self.code.len()
+        {
+            return Err(malformed(offset, "instruction operands run past the end of the code."));
+        }
+
+        Ok(())
+    }
+
+    fn check_constant(&self, offset: usize, width: usize, is_name: bool) -> Result<Value, ChunkError>
+    {
+        self.check_operands(offset, width)?;
+
+        let index = (1..=width).fold(0, |index, i| (index << 8) | self.code[offset + i] as usize);
+        let constant = match self.constants.values.get(index)
+        {
+            Some(constant) => *constant,
+            None => return Err(malformed(offset, &format!("constant {} is out of range.", index))),
+        };
+
+        if is_name && !constant.is_string()
+        {
+            return Err(malformed(offset, "name constant is not a string."));
+        }
+
+        Ok(constant)
+    }
+}
+
+fn malformed(offset: usize, message: &str) -> ChunkError
+{
+    ChunkError::Malformed(format!("at offset {}, {}", offset, message))
+}
+
+fn write_constant<W: Write>(writer: &mut W, value: Value) -> Result<(), ChunkError>
+{
+    match value
+    {
+        Value::Nil => writer.write_all(&[TAG_NIL])?,
+        Value::Bool(value) => writer.write_all(&[TAG_BOOL, value as u8])?,
+        Value::Number(value) =>
+        {
+            writer.write_all(&[TAG_NUMBER])?;
+            writer.write_all(&value.to_le_bytes())?;
+        }
+        Value::Obj(object) => match &object.type_of
+        {
+            ObjType::String(string) =>
+            {
+                writer.write_all(&[TAG_STRING])?;
+                write_string(writer, &string.chars)?;
+            }
+            ObjType::Function(function) =>
+            {
+                writer.write_all(&[TAG_FUNCTION])?;
+                write_u32(writer, function.arity)?;
+                write_u32(writer, function.upvalue_count)?;
+
+                match function.name
+                {
+                    Some(name) =>
+                    {
+                        writer.write_all(&[1])?;
+                        write_string(writer, &name.as_string().chars)?;
+                    }
+                    None => writer.write_all(&[0])?,
+                }
+
+                function.chunk.write_body(writer)?;
+            }
+            // Only literals and functions ever end up in a constant pool
+            _ => return Err(ChunkError::Malformed(format!("cannot serialize constant '{}'.", object))),
+        },
+    }
+
+    Ok(())
+}
+
+fn read_constant<R: Read>(reader: &mut R, vm: &mut VM) -> Result<Value, ChunkError>
+{
+    let tag = read_bytes(reader, 1)?[0];
+
+    let value = match tag
+    {
+        TAG_NIL => Value::Nil,
+        TAG_BOOL => match read_bytes(reader, 1)?[0]
+        {
+            0 => Value::Bool(false),
+            1 => Value::Bool(true),
+            byte => return Err(ChunkError::Malformed(format!("invalid boolean {}.", byte))),
+        },
+        TAG_NUMBER =>
+        {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Value::Number(f64::from_le_bytes(bytes))
+        }
+        TAG_STRING =>
+        {
+            let string = read_string(reader, vm)?;
+            Value::Obj(string)
+        }
+        TAG_FUNCTION =>
+        {
+            let mut function = ObjFunction::new();
+            function.arity = read_u32(reader)?;
+            function.upvalue_count = read_u32(reader)?;
+
+            function.name = match read_bytes(reader, 1)?[0]
+            {
+                0 => None,
+                1 => Some(read_string(reader, vm)?),
+                byte => return Err(ChunkError::Malformed(format!("invalid function name flag {}.", byte))),
+            };
+
+            function.chunk = Chunk::read_body(reader, vm)?;
+            function.chunk.validate(function.arity + 1, function.upvalue_count)?;
+
+            let function = vm.new_function(function);
+            vm.compiler_roots.push(function);
+            Value::Obj(function)
+        }
+        _ => return Err(ChunkError::Malformed(format!("unknown constant tag {}.", tag))),
+    };
+
+    Ok(value)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: usize) -> Result<(), ChunkError>
+{
+    if value > u32::MAX as usize
+    {
+        return Err(ChunkError::Malformed(format!("{} does not fit in 32 bits.", value)));
+    }
+
+    writer.write_all(&(value as u32).to_le_bytes())?;
+    Ok(())
+}
+
+fn write_string<W: Write>(writer: &mut W, string: &str) -> Result<(), ChunkError>
+{
+    write_u32(writer, string.len())?;
+    writer.write_all(string.as_bytes())?;
+    Ok(())
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> Result<u16, ChunkError>
+{
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<usize, ChunkError>
+{
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes) as usize)
+}
+
+// Reads through take() so a corrupt length can't make us allocate a huge buffer up front
+fn read_bytes<R: Read>(reader: &mut R, length: usize) -> Result<Vec<u8>, ChunkError>
+{
+    let mut bytes = Vec::new();
+    reader.by_ref().take(length as u64).read_to_end(&mut bytes)?;
+
+    if bytes.len() != length
+    {
+        return Err(ChunkError::Truncated);
+    }
+
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R, vm: &mut VM) -> Result<ObjRef, ChunkError>
+{
+    let length = read_u32(reader)?;
+    let bytes = read_bytes(reader, length)?;
+
+    let chars = match String::from_utf8(bytes)
+    {
+        Ok(chars) => chars,
+        Err(_) => return Err(ChunkError::Malformed("string constant is not valid UTF-8.".to_string())),
+    };
+
+    let string = vm.take_string(chars);
+    vm.compiler_roots.push(string);
+    Ok(string)
+}
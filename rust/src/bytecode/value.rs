@@ -1,5 +1,99 @@
-// Temporary implementation of Value
-pub type Value = f64;
+use std::fmt;
+
+use super::object::ObjRef;
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Value
+{
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Obj(ObjRef),
+}
+
+impl Value
+{
+    pub fn is_nil(&self) -> bool
+    {
+        matches!(self, Value::Nil)
+    }
+
+    pub fn is_bool(&self) -> bool
+    {
+        matches!(self, Value::Bool(_))
+    }
+
+    pub fn is_number(&self) -> bool
+    {
+        matches!(self, Value::Number(_))
+    }
+
+    pub fn is_obj(&self) -> bool
+    {
+        matches!(self, Value::Obj(_))
+    }
+
+    pub fn is_string(&self) -> bool
+    {
+        match self
+        {
+            Value::Obj(obj) => obj.is_string(),
+            _ => false,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool>
+    {
+        match self
+        {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64>
+    {
+        match self
+        {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_obj(&self) -> Option<ObjRef>
+    {
+        match self
+        {
+            Value::Obj(obj) => Some(*obj),
+            _ => None,
+        }
+    }
+
+    // null and false are the only falsey values, everything else is truthy
+    pub fn is_falsey(&self) -> bool
+    {
+        match self
+        {
+            Value::Nil => true,
+            Value::Bool(value) => !value,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Value::Nil => write!(f, "null"),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", value),
+            Value::Obj(obj) => write!(f, "{}", obj),
+        }
+    }
+}
 
 pub struct ValueArray
 {
@@ -30,4 +124,4 @@ impl ValueArray
 pub fn print_value(value: Value)
 {
     print!("{}", value);
-}
\ No newline at end of file
+}
@@ -1,49 +1,180 @@
 #![allow(dead_code)]
 
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+};
+
+use crate::diagnostics;
+
 use super::{
     debug,
-    scanner::Scanner,
-    compiler::Parser,
+    native,
+    memory::GC_INITIAL_THRESHOLD,
+    compiler::{CompileError, Parser},
     chunk::{Chunk, OpCode},
+    object::{NativeFn, ObjFunction, ObjRef, ObjType, UpvalueLocation},
     value::{self, Value},
 };
 
 
-const STACK_MAX: usize = 256;
+const FRAMES_MAX: usize = 64;
+const STACK_MAX: usize = FRAMES_MAX * (u8::MAX as usize + 1);
 
 
-pub struct VM
+pub(super) struct CallFrame
 {
-    pub(super) chunk: Chunk,
+    pub(super) closure: ObjRef,
     pub(super) ip: usize,
-    stack: Vec<Value>,
+    // Index of the first stack slot this frame can use
+    pub(super) slots: usize,
+}
+
+pub struct VM
+{
+    pub(super) frames: Vec<CallFrame>,
+    pub(super) stack: Vec<Value>,
+    pub(super) globals: HashMap<ObjRef, Value>,
+    // Upvalues still pointing into the stack, ordered by stack slot
+    pub(super) open_upvalues: Vec<ObjRef>,
+    pub(super) strings: HashMap<String, ObjRef>,
+    pub(super) init_string: Option<ObjRef>,
+    pub(super) objects: Vec<ObjRef>,
+    pub(super) gray_stack: Vec<ObjRef>,
+    // Objects the compiler has created but the VM cannot see yet
+    pub(super) compiler_roots: Vec<ObjRef>,
+    pub(super) bytes_allocated: usize,
+    pub(super) next_gc: usize,
+    // Where print statements go, stdout unless replaced
+    pub(super) output: Box<dyn Write>,
+    // Command line arguments, the script's own path first
+    pub(super) args: Vec<String>,
 }
 
 pub enum InterpretResult
 {
     Okay,
-    CompilerError,
-    RuntimeError,
+    CompilerError(Vec<CompileError>),
+    RuntimeError(RuntimeError),
+}
+
+// One entry of a runtime stack trace, innermost call first
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame
+{
+    // None for the top-level script
+    pub function: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError
+{
+    pub message: String,
+    // Position of the instruction that failed
+    pub line: usize,
+    pub column: usize,
+    pub trace: Vec<TraceFrame>,
+}
+
+impl RuntimeError
+{
+    // The line that failed with a caret under the instruction's token
+    pub fn excerpt(&self, source: &str) -> String
+    {
+        diagnostics::excerpt(source, self.line, self.column, 1)
+    }
 }
 
+impl fmt::Display for TraceFrame
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match &self.function
+        {
+            Some(name) => write!(f, "[line {}:{}] in {}()", self.line, self.column, name),
+            None => write!(f, "[line {}:{}] in script", self.line, self.column),
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.message)?;
+
+        for frame in &self.trace
+        {
+            write!(f, "\n{}", frame)?;
+        }
+
+        Ok(())
+    }
+}
+
+// Named like the opcodes they run
+#[allow(clippy::upper_case_acronyms)]
 enum BinaryOp
 {
     ADD,
     SUB,
     MUL,
     DIV,
+    GREATER,
+    GEQ,
+    LESS,
+    LEQ,
+}
+
+impl CallFrame
+{
+    fn chunk(&self) -> &Chunk
+    {
+        &self.closure.as_closure().function.as_function().chunk
+    }
 }
 
 impl VM
 {
     pub fn new() -> VM
     {
-        VM
+        let mut vm = VM
         {
-            chunk: Chunk::new(),
-            ip: 0,
+            frames: Vec::with_capacity(FRAMES_MAX),
             stack: Vec::new(),
-        }
+            globals: HashMap::new(),
+            open_upvalues: Vec::new(),
+            strings: HashMap::new(),
+            init_string: None,
+            objects: Vec::new(),
+            gray_stack: Vec::new(),
+            compiler_roots: Vec::new(),
+            bytes_allocated: 0,
+            next_gc: GC_INITIAL_THRESHOLD,
+            output: Box::new(io::stdout()),
+            args: Vec::new(),
+        };
+
+        vm.init_string = Some(vm.copy_string("init"));
+        vm.define_native("clock", 0, native::clock);
+        vm.define_native("argc", 0, native::argc);
+        vm.define_native("argv", 1, native::argv);
+        vm
+    }
+
+    // Lets the output of a script be captured, ie. by tests
+    pub fn set_output(&mut self, output: Box<dyn Write>)
+    {
+        self.output = output;
+    }
+
+    // What argc and argv give the script
+    pub fn set_args(&mut self, args: Vec<String>)
+    {
+        self.args = args;
     }
 
     pub fn init(&mut self)
@@ -51,85 +182,410 @@ impl VM
         self.reset_stack();
     }
 
-    pub fn free(&self)
+    pub fn free(&mut self)
     {
-
+        self.free_objects();
     }
 
     pub fn reset_stack(&mut self)
     {
+        // Closures that escaped, ie. into a global, still need their values
+        self.close_upvalues(0);
+
         self.stack.clear();
         self.stack.reserve(STACK_MAX);
+        self.frames.clear();
+        self.open_upvalues.clear();
     }
 
     pub fn interpret(&mut self, source: String) -> InterpretResult
     {
-        let mut parser = Parser::new();
-        let chunk = Chunk::new();
-
-        if !parser.compile(source, chunk)
+        match self.compile(source)
         {
-            return InterpretResult::CompilerError;
+            Ok(chunk) => self.interpret_chunk(chunk),
+            Err(errors) => InterpretResult::CompilerError(errors),
         }
+    }
 
+    pub fn compile(&mut self, source: String) -> Result<Chunk, Vec<CompileError>>
+    {
+        let mut parser = Parser::new(self);
+        parser.compile(source, Chunk::new())
+    }
+
+    // Runs an already compiled script, ie. one loaded from a bytecode file
+    pub fn interpret_chunk(&mut self, chunk: Chunk) -> InterpretResult
+    {
         self.init();
-        //self.chunk = chunk;
 
-        let result = self.run();
-        
-        self.free();
-        result
+        let mut script = ObjFunction::new();
+        script.chunk = chunk;
+
+        let function = self.new_function(script);
+        self.push(Value::Obj(function));
+        let closure = self.new_closure(function, Vec::new());
+        self.pop();
+        self.push(Value::Obj(closure));
+
+        if let Err(result) = self.call(closure, 0)
+        {
+            return result;
+        }
+
+        self.run()
     }
 
-    fn push(&mut self, value: Value)
+    // Whatever is left on the stack, ie. after a runtime error
+    pub fn stack(&self) -> &[Value]
+    {
+        &self.stack
+    }
+
+    // Every global by name, sorted
+    pub fn globals(&self) -> Vec<(String, Value)>
+    {
+        let mut globals: Vec<(String, Value)> = self.globals.iter()
+            .map(|(name, value)| (name.as_string().chars.clone(), *value))
+            .collect();
+
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
+    }
+
+    // What the collector counts as live, ie. to check it paces itself
+    pub fn bytes_allocated(&self) -> usize
+    {
+        self.bytes_allocated
+    }
+
+    // Exposes a Rust function to scripts as a global
+    pub fn define_native(&mut self, name: &str, arity: usize, function: NativeFn)
+    {
+        let name = self.copy_string(name);
+        let native = self.new_native(name, arity, function);
+        self.globals.insert(name, Value::Obj(native));
+    }
+
+    pub(super) fn push(&mut self, value: Value)
     {
         self.stack.push(value);
     }
 
-    fn pop(&mut self) -> Option<Value>
+    // The compiler always balances the stack, so running dry is a bug in the VM itself
+    pub(super) fn pop(&mut self) -> Value
     {
-        self.stack.pop()
+        self.stack.pop().expect("VM stack underflow.")
     }
 
-    fn read_byte(&mut self) -> OpCode
+    fn peek(&self, distance: usize) -> Value
     {
-        self.ip += 1;
-        OpCode::from(self.chunk.code[self.ip - 1])
+        self.stack[self.stack.len() - 1 - distance]
     }
 
-    fn read_constant(&mut self) -> Value
+    fn frame(&self) -> &CallFrame
     {
-        let pos = self.read_byte() as u8;
-        self.chunk.constants.values[pos as usize]
+        self.frames.last().unwrap()
     }
 
-    // Can probably turn this into a macro
-    fn binary_op(&mut self, op: BinaryOp)
+    fn frame_mut(&mut self) -> &mut CallFrame
     {
-        use BinaryOp::*;
-        match op
+        self.frames.last_mut().unwrap()
+    }
+
+    fn chunk(&self) -> &Chunk
+    {
+        self.frame().chunk()
+    }
+
+    fn read_byte(&mut self) -> u8
+    {
+        let frame = self.frame_mut();
+        frame.ip += 1;
+        frame.chunk().code[frame.ip - 1]
+    }
+
+    fn read_short(&mut self) -> u16
+    {
+        let frame = self.frame_mut();
+        frame.ip += 2;
+
+        let code = &frame.chunk().code;
+        ((code[frame.ip - 2] as u16) << 8) | code[frame.ip - 1] as u16
+    }
+
+    fn read_long(&mut self) -> usize
+    {
+        let frame = self.frame_mut();
+        frame.ip += 3;
+
+        let code = &frame.chunk().code;
+        ((code[frame.ip - 3] as usize) << 16) | ((code[frame.ip - 2] as usize) << 8) | code[frame.ip - 1] as usize
+    }
+
+    // Long forms of an instruction have a 24-bit constant index
+    fn read_constant(&mut self, long: bool) -> Value
+    {
+        let pos = if long { self.read_long() } else { self.read_byte() as usize };
+        self.chunk().constants.values[pos]
+    }
+
+    fn read_string(&mut self, long: bool) -> ObjRef
+    {
+        match self.read_constant(long)
         {
-            ADD => {
-                let b = self.pop().unwrap();
-                let a = self.pop().unwrap();
-                self.push(a + b);
-            }
-            SUB => {
-                let b = self.pop().unwrap();
-                let a = self.pop().unwrap();
-                self.push(a - b);
+            Value::Obj(string) => string,
+            _ => unreachable!(),
+        }
+    }
+
+    // Captures the call stack for the error. The VM stack is left as it was,
+    // so the REPL's :stack can show it, and the next run resets it
+    fn runtime_error(&mut self, message: String) -> InterpretResult
+    {
+        let trace: Vec<TraceFrame> = self.frames.iter().rev()
+            .map(|frame|
+            {
+                let function = frame.closure.as_closure().function.as_function();
+
+                TraceFrame
+                {
+                    function: function.name.map(|name| name.as_string().chars.clone()),
+                    line: function.chunk.get_line(frame.ip - 1),
+                    column: function.chunk.get_column(frame.ip - 1),
+                }
+            })
+            .collect();
+
+        let (line, column) = trace.first().map_or((0, 0), |frame| (frame.line, frame.column));
+
+        InterpretResult::RuntimeError(RuntimeError { message, line, column, trace })
+    }
+
+    fn call_value(&mut self, callee: Value, arg_count: usize) -> Result<(), InterpretResult>
+    {
+        if let Value::Obj(object) = callee
+        {
+            match &object.type_of
+            {
+                ObjType::BoundMethod(bound) =>
+                {
+                    // The receiver takes the place of the callee, so it lands in slot zero as 'this'
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = bound.receiver;
+                    return self.call(bound.method, arg_count);
+                }
+                ObjType::Class(class) =>
+                {
+                    let instance = self.new_instance(object);
+                    let slot = self.stack.len() - arg_count - 1;
+                    self.stack[slot] = Value::Obj(instance);
+
+                    let initializer = class.methods.borrow().get(&self.init_string.unwrap()).copied();
+                    match initializer
+                    {
+                        Some(Value::Obj(initializer)) => return self.call(initializer, arg_count),
+                        _ if arg_count != 0 =>
+                        {
+                            return Err(self.runtime_error(format!("Expected 0 arguments but got {}.", arg_count)));
+                        }
+                        _ => return Ok(()),
+                    }
+                }
+                ObjType::Closure(_) => return self.call(object, arg_count),
+                ObjType::Native(native) =>
+                {
+                    if arg_count != native.arity
+                    {
+                        return Err(self.runtime_error(format!("Expected {} arguments but got {}.", native.arity, arg_count)));
+                    }
+
+                    let args_start = self.stack.len() - arg_count;
+                    let args: Vec<Value> = self.stack[args_start..].to_vec();
+
+                    let result = match (native.function)(self, &args)
+                    {
+                        Ok(result) => result,
+                        Err(message) => return Err(self.runtime_error(message)),
+                    };
+
+                    // Discard the arguments and the native itself
+                    self.stack.truncate(args_start - 1);
+                    self.push(result);
+                    return Ok(());
+                }
+                _ => {}
             }
-            MUL => {
-                let b = self.pop().unwrap();
-                let a = self.pop().unwrap();
-                self.push(a * b);
+        }
+
+        Err(self.runtime_error("Can only call functions and classes.".to_string()))
+    }
+
+    fn call(&mut self, closure: ObjRef, arg_count: usize) -> Result<(), InterpretResult>
+    {
+        let arity = closure.as_closure().function.as_function().arity;
+        if arg_count != arity
+        {
+            return Err(self.runtime_error(format!("Expected {} arguments but got {}.", arity, arg_count)));
+        }
+
+        if self.frames.len() == FRAMES_MAX
+        {
+            return Err(self.runtime_error("Stack overflow.".to_string()));
+        }
+
+        self.frames.push(CallFrame
+        {
+            closure,
+            ip: 0,
+            slots: self.stack.len() - arg_count - 1,
+        });
+
+        Ok(())
+    }
+
+    fn invoke_from_class(&mut self, class: ObjRef, name: ObjRef, arg_count: usize) -> Result<(), InterpretResult>
+    {
+        let method = class.as_class().methods.borrow().get(&name).copied();
+        match method
+        {
+            Some(Value::Obj(method)) => self.call(method, arg_count),
+            _ => Err(self.runtime_error(format!("Undefined property '{}'.", name))),
+        }
+    }
+
+    fn invoke(&mut self, name: ObjRef, arg_count: usize) -> Result<(), InterpretResult>
+    {
+        let receiver = match self.peek(arg_count)
+        {
+            Value::Obj(receiver) if receiver.is_instance() => receiver,
+            _ => return Err(self.runtime_error("Only instances have methods.".to_string())),
+        };
+
+        // Fields shadow methods, and may hold anything callable
+        let instance = receiver.as_instance();
+        let field = instance.fields.borrow().get(&name).copied();
+        if let Some(value) = field
+        {
+            let slot = self.stack.len() - arg_count - 1;
+            self.stack[slot] = value;
+            return self.call_value(value, arg_count);
+        }
+
+        self.invoke_from_class(instance.class, name, arg_count)
+    }
+
+    fn bind_method(&mut self, class: ObjRef, name: ObjRef) -> Result<(), InterpretResult>
+    {
+        let method = class.as_class().methods.borrow().get(&name).copied();
+        let method = match method
+        {
+            Some(Value::Obj(method)) => method,
+            _ => return Err(self.runtime_error(format!("Undefined property '{}'.", name))),
+        };
+
+        let bound = self.new_bound_method(self.peek(0), method);
+        self.pop();
+        self.push(Value::Obj(bound));
+        Ok(())
+    }
+
+    // Calls assume every method is a closure, so a hand written chunk can't add anything else
+    fn define_method(&mut self, name: ObjRef) -> Result<(), InterpretResult>
+    {
+        match (self.peek(1), self.peek(0))
+        {
+            (Value::Obj(class), Value::Obj(method)) if class.is_class() && method.is_closure() =>
+            {
+                class.as_class().methods.borrow_mut().insert(name, Value::Obj(method));
             }
-            DIV => {
-                let b = self.pop().unwrap();
-                let a = self.pop().unwrap();
-                self.push(a / b);
+            _ => return Err(self.runtime_error("Methods must be closures defined on a class.".to_string())),
+        }
+
+        self.pop();
+        Ok(())
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> ObjRef
+    {
+        // Reuse an existing upvalue so closures share the same variable
+        let mut insert_at = self.open_upvalues.len();
+        for (i, upvalue) in self.open_upvalues.iter().enumerate()
+        {
+            if let UpvalueLocation::Stack(location) = upvalue.as_upvalue().location.get()
+            {
+                if location == slot { return *upvalue; }
+                if location > slot
+                {
+                    insert_at = i;
+                    break;
+                }
             }
         }
+
+        let created = self.new_upvalue(slot);
+        self.open_upvalues.insert(insert_at, created);
+        created
+    }
+
+    // Moves every open upvalue at or above the given slot off the stack
+    fn close_upvalues(&mut self, last: usize)
+    {
+        while let Some(upvalue) = self.open_upvalues.last()
+        {
+            let upvalue = upvalue.as_upvalue();
+            let slot = match upvalue.location.get()
+            {
+                UpvalueLocation::Stack(slot) => slot,
+                UpvalueLocation::Closed(_) => unreachable!(),
+            };
+
+            if slot < last { break; }
+
+            upvalue.location.set(UpvalueLocation::Closed(self.stack[slot]));
+            self.open_upvalues.pop();
+        }
+    }
+
+    fn concatenate(&mut self)
+    {
+        let b = self.pop();
+        let a = self.pop();
+
+        let chars = match (a, b)
+        {
+            (Value::Obj(a), Value::Obj(b)) => format!("{}{}", a.as_string().chars, b.as_string().chars),
+            _ => unreachable!(),
+        };
+
+        let result = self.take_string(chars);
+        self.push(Value::Obj(result));
+    }
+
+    fn binary_op(&mut self, op: BinaryOp) -> Result<(), InterpretResult>
+    {
+        let (a, b) = match (self.peek(1), self.peek(0))
+        {
+            (Value::Number(a), Value::Number(b)) => (a, b),
+            _ => return Err(self.runtime_error("Operands must be numbers.".to_string())),
+        };
+
+        self.pop();
+        self.pop();
+
+        use BinaryOp::*;
+        match op
+        {
+            ADD => self.push(Value::Number(a + b)),
+            SUB => self.push(Value::Number(a - b)),
+            MUL => self.push(Value::Number(a * b)),
+            DIV => self.push(Value::Number(a / b)),
+            GREATER => self.push(Value::Bool(a > b)),
+            GEQ => self.push(Value::Bool(a >= b)),
+            LESS => self.push(Value::Bool(a < b)),
+            LEQ => self.push(Value::Bool(a <= b)),
+        }
+
+        Ok(())
     }
 
     fn run(&mut self) -> InterpretResult
@@ -150,42 +606,336 @@ impl VM
                 println!();
 
                 // Show instruction
-                debug::disassemble_instruction(&self.chunk, self.ip);
+                debug::disassemble_instruction(self.chunk(), self.frame().ip);
             }
 
-            let instruction = self.read_byte();
+            let (instruction, long) = OpCode::from(self.read_byte()).short_form();
 
             match instruction
             {
                 Constant =>
                 {
-                    let constant = self.read_constant();
+                    let constant = self.read_constant(long);
                     self.push(constant);
                 }
+                Nil => self.push(Value::Nil),
+                True => self.push(Value::Bool(true)),
+                False => self.push(Value::Bool(false)),
+                Equal =>
+                {
+                    let b = self.pop();
+                    let a = self.pop();
+                    self.push(Value::Bool(a == b));
+                }
+                Greater => if let Err(result) = self.binary_op(BinaryOp::GREATER) { return result; },
+                GreaterEqual => if let Err(result) = self.binary_op(BinaryOp::GEQ) { return result; },
+                Less => if let Err(result) = self.binary_op(BinaryOp::LESS) { return result; },
+                LessEqual => if let Err(result) = self.binary_op(BinaryOp::LEQ) { return result; },
+                Not =>
+                {
+                    let value = self.pop();
+                    self.push(Value::Bool(value.is_falsey()));
+                }
                 Negate =>
                 {
-                    if let Some(val) = self.pop()
+                    let value = match self.peek(0)
                     {
-                        self.push(-val);
+                        Value::Number(value) => value,
+                        _ => return self.runtime_error("Operand must be a number.".to_string()),
+                    };
+
+                    self.pop();
+                    self.push(Value::Number(-value));
+                }
+
+                Add =>
+                {
+                    match (self.peek(1), self.peek(0))
+                    {
+                        (Value::Obj(a), Value::Obj(b)) if a.is_string() && b.is_string() => self.concatenate(),
+                        (Value::Number(_), Value::Number(_)) =>
+                        {
+                            if let Err(result) = self.binary_op(BinaryOp::ADD) { return result; }
+                        }
+                        _ => return self.runtime_error("Operands must be two numbers or two strings.".to_string()),
                     }
                 }
+                Subtract => if let Err(result) = self.binary_op(BinaryOp::SUB) { return result; },
+                Multiply => if let Err(result) = self.binary_op(BinaryOp::MUL) { return result; },
+                Divide => if let Err(result) = self.binary_op(BinaryOp::DIV) { return result; },
 
-                Add => self.binary_op(BinaryOp::ADD),
-                Subtract => self.binary_op(BinaryOp::SUB),
-                Multiply => self.binary_op(BinaryOp::MUL),
-                Divide => self.binary_op(BinaryOp::DIV),
+                Pop => { self.pop(); }
+                GetLocal =>
+                {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.push(self.stack[slot]);
+                }
+                SetLocal =>
+                {
+                    let slot = self.frame().slots + self.read_byte() as usize;
+                    self.stack[slot] = self.peek(0);
+                }
+                GetGlobal =>
+                {
+                    let name = self.read_string(long);
+                    let value = match self.globals.get(&name)
+                    {
+                        Some(value) => *value,
+                        None => return self.runtime_error(format!("Undefined variable '{}'.", name)),
+                    };
+                    self.push(value);
+                }
+                DefineGlobal =>
+                {
+                    let name = self.read_string(long);
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+                SetGlobal =>
+                {
+                    // Assignment is an expression, so the value is left on the stack
+                    let name = self.read_string(long);
+                    let value = self.peek(0);
+                    match self.globals.get_mut(&name)
+                    {
+                        Some(global) => *global = value,
+                        None => return self.runtime_error(format!("Undefined variable '{}'.", name)),
+                    }
+                }
+                GetUpvalue =>
+                {
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.as_closure().upvalues[slot];
 
-                Return => 
+                    let value = match upvalue.as_upvalue().location.get()
+                    {
+                        UpvalueLocation::Stack(location) => self.stack[location],
+                        UpvalueLocation::Closed(value) => value,
+                    };
+                    self.push(value);
+                }
+                SetUpvalue =>
                 {
-                    if let Some(val) = self.pop()
+                    let slot = self.read_byte() as usize;
+                    let upvalue = self.frame().closure.as_closure().upvalues[slot];
+                    let value = self.peek(0);
+
+                    let location = &upvalue.as_upvalue().location;
+                    match location.get()
                     {
-                        value::print_value(val);
-                        println!();
+                        UpvalueLocation::Stack(slot) => self.stack[slot] = value,
+                        UpvalueLocation::Closed(_) => location.set(UpvalueLocation::Closed(value)),
+                    }
+                }
+                GetProperty =>
+                {
+                    let instance = match self.peek(0)
+                    {
+                        Value::Obj(instance) if instance.is_instance() => instance,
+                        _ => return self.runtime_error("Only instances have properties.".to_string()),
+                    };
+                    let name = self.read_string(long);
+
+                    let field = instance.as_instance().fields.borrow().get(&name).copied();
+                    match field
+                    {
+                        Some(value) =>
+                        {
+                            self.pop();
+                            self.push(value);
+                        }
+                        None =>
+                        {
+                            if let Err(result) = self.bind_method(instance.as_instance().class, name)
+                            {
+                                return result;
+                            }
+                        }
+                    }
+                }
+                SetProperty =>
+                {
+                    let instance = match self.peek(1)
+                    {
+                        Value::Obj(instance) if instance.is_instance() => instance,
+                        _ => return self.runtime_error("Only instances have fields.".to_string()),
+                    };
+                    let name = self.read_string(long);
+
+                    instance.as_instance().fields.borrow_mut().insert(name, self.peek(0));
+
+                    // Leave the assigned value as the result of the expression
+                    let value = self.pop();
+                    self.pop();
+                    self.push(value);
+                }
+                GetSuper =>
+                {
+                    let name = self.read_string(long);
+                    // Only a hand written chunk could have anything else here
+                    let superclass = match self.pop()
+                    {
+                        Value::Obj(superclass) if superclass.is_class() => superclass,
+                        _ => return self.runtime_error("Superclass must be a class.".to_string()),
+                    };
+
+                    if let Err(result) = self.bind_method(superclass, name)
+                    {
+                        return result;
+                    }
+                }
+                Print =>
+                {
+                    let value = self.pop();
+                    writeln!(self.output, "{}", value).expect("Failed to write output.");
+                }
+
+                Jump =>
+                {
+                    let offset = self.read_short();
+                    self.frame_mut().ip += offset as usize;
+                }
+                JumpIfFalse =>
+                {
+                    let offset = self.read_short();
+                    if self.peek(0).is_falsey()
+                    {
+                        self.frame_mut().ip += offset as usize;
+                    }
+                }
+                Loop =>
+                {
+                    let offset = self.read_short();
+                    self.frame_mut().ip -= offset as usize;
+                }
+
+                Call =>
+                {
+                    let arg_count = self.read_byte() as usize;
+                    if let Err(result) = self.call_value(self.peek(arg_count), arg_count)
+                    {
+                        return result;
+                    }
+                }
+                Invoke =>
+                {
+                    let method = self.read_string(long);
+                    let arg_count = self.read_byte() as usize;
+                    if let Err(result) = self.invoke(method, arg_count)
+                    {
+                        return result;
+                    }
+                }
+                SuperInvoke =>
+                {
+                    let method = self.read_string(long);
+                    let arg_count = self.read_byte() as usize;
+                    // Only a hand written chunk could have anything else here
+                    let superclass = match self.pop()
+                    {
+                        Value::Obj(superclass) if superclass.is_class() => superclass,
+                        _ => return self.runtime_error("Superclass must be a class.".to_string()),
+                    };
+
+                    if let Err(result) = self.invoke_from_class(superclass, method, arg_count)
+                    {
+                        return result;
+                    }
+                }
+                Closure =>
+                {
+                    let function = match self.read_constant(long)
+                    {
+                        Value::Obj(function) => function,
+                        _ => unreachable!(),
+                    };
+
+                    let upvalue_count = function.as_function().upvalue_count;
+                    let mut upvalues = Vec::with_capacity(upvalue_count);
+
+                    for _ in 0..upvalue_count
+                    {
+                        let is_local = self.read_byte() == 1;
+                        let index = self.read_byte() as usize;
+
+                        if is_local
+                        {
+                            let slot = self.frame().slots + index;
+                            upvalues.push(self.capture_upvalue(slot));
+                        }
+                        else
+                        {
+                            upvalues.push(self.frame().closure.as_closure().upvalues[index]);
+                        }
+                    }
+
+                    let closure = self.new_closure(function, upvalues);
+                    self.push(Value::Obj(closure));
+                }
+                CloseUpvalue =>
+                {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.pop();
+                }
+                Return =>
+                {
+                    let result = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.close_upvalues(frame.slots);
+
+                    if self.frames.is_empty()
+                    {
+                        // Pop the script function itself
+                        self.pop();
+                        return InterpretResult::Okay;
+                    }
+
+                    self.stack.truncate(frame.slots);
+                    self.push(result);
+                }
+                Class =>
+                {
+                    let name = self.read_string(long);
+                    let class = self.new_class(name);
+                    self.push(Value::Obj(class));
+                }
+                Inherit =>
+                {
+                    let superclass = match self.peek(1)
+                    {
+                        Value::Obj(superclass) if superclass.is_class() => superclass,
+                        _ => return self.runtime_error("Superclass must be a class.".to_string()),
+                    };
+
+                    let subclass = match self.peek(0)
+                    {
+                        Value::Obj(subclass) if subclass.is_class() => subclass,
+                        _ => return self.runtime_error("Subclass must be a class.".to_string()),
+                    };
+
+                    // Copy down the inherited methods, subclass methods are defined after and override them
+                    let methods = superclass.as_class().methods.borrow().clone();
+                    subclass.as_class().methods.borrow_mut().extend(methods);
+                    self.pop();
+                }
+                Method =>
+                {
+                    let name = self.read_string(long);
+                    if let Err(result) = self.define_method(name)
+                    {
+                        return result;
                     }
-                    return InterpretResult::Okay;
                 }
                 _ => {}
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl Drop for VM
+{
+    fn drop(&mut self)
+    {
+        self.free();
+    }
+}
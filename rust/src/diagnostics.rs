@@ -0,0 +1,32 @@
+/*
+    Source excerpts for error messages, shared by both interpreters
+
+       3 |     print 1 + "x";
+         |             ^
+*/
+
+// Lines and columns start at 1 and columns count characters, not bytes.
+// Returns an empty string if the line is not in the source.
+pub fn excerpt(source: &str, line: usize, column: usize, width: usize) -> String
+{
+    let text = match source.lines().nth(line.wrapping_sub(1))
+    {
+        Some(text) => text.trim_end_matches('\r'),
+        None => return String::new(),
+    };
+
+    let gutter = line.to_string().len();
+    let column = column.max(1);
+
+    // Keep tabs from the line so the caret lines up however the terminal renders them
+    let padding: String = text.chars()
+        .take(column - 1)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    // Spans that run on past the end of the line are cut off there
+    let remaining = text.chars().count().saturating_sub(column - 1);
+    let carets = "^".repeat(width.min(remaining).max(1));
+
+    format!("{:>gutter$} | {}\n{:>gutter$} | {}{}", line, text, "", padding, carets, gutter = gutter)
+}
@@ -0,0 +1,30 @@
+use super::token::TokenType;
+
+// The only list of reserved words, both front ends go through here
+pub fn keyword(text: &str) -> Option<TokenType>
+{
+    use TokenType::*;
+
+    let type_of = match text
+    {
+        "and" => And,
+        "class" => Class,
+        "else" => Else,
+        "false" => False,
+        "for" => For,
+        "fn" => Func,
+        "if" => If,
+        "null" => Null,
+        "or" => Or,
+        "print" => Print,
+        "return" => Return,
+        "parent" => Super,
+        "this" => This,
+        "true" => True,
+        "let" => Var,
+        "while" => While,
+        _ => return None,
+    };
+
+    Some(type_of)
+}
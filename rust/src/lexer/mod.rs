@@ -0,0 +1,3 @@
+pub mod token;
+pub mod keywords;
+pub mod scanner;
@@ -0,0 +1,357 @@
+use std::{
+    iter::Peekable,
+    str::Chars,
+};
+
+use super::{
+    keywords,
+    token::{Span, Token, TokenType},
+};
+
+// Hands out one token at a time, walking the source with a byte cursor that
+// always sits on a char boundary. Comments come out as tokens too, so the
+// formatter can keep them, and the parsers skip them.
+pub struct Scanner
+{
+    source: String,
+    start: usize,
+    current: usize,
+    line: usize,
+    // Characters consumed so far on the current line
+    column: usize,
+    start_line: usize,
+    start_column: usize,
+    error_message: String,
+    // Whether the last Error token ran into the end of the source
+    error_incomplete: bool,
+}
+
+impl Default for Scanner
+{
+    fn default() -> Self
+    {
+        Scanner::new()
+    }
+}
+
+impl Scanner
+{
+    pub fn new() -> Scanner
+    {
+        Scanner
+        {
+            source: String::new(),
+            start: 0,
+            current: 0,
+            line: 1,
+            column: 0,
+            start_line: 1,
+            start_column: 1,
+            error_message: String::new(),
+            error_incomplete: false,
+        }
+    }
+
+    pub fn init(&mut self, source: String)
+    {
+        self.source = source;
+        self.start = 0;
+        self.current = 0;
+        self.line = 1;
+        self.column = 0;
+    }
+
+    pub fn lexeme(&self, token: &Token) -> &str
+    {
+        token.lexeme(&self.source)
+    }
+
+    // The reason the last Error token was produced
+    pub fn error_message(&self) -> &str
+    {
+        &self.error_message
+    }
+
+    // True when the last Error token only needed more input, ie. an open string
+    pub fn error_is_incomplete(&self) -> bool
+    {
+        self.error_incomplete
+    }
+
+    // Keeps returning EOF once the source runs out
+    pub fn scan_token(&mut self) -> Token
+    {
+        self.skip_whitespace();
+
+        self.start = self.current;
+        self.start_line = self.line;
+        self.start_column = self.column + 1;
+
+        if self.is_at_end() { return self.make_token(TokenType::EOF); }
+
+        let c = self.advance();
+
+        if Scanner::is_alpha(c) { return self.identifier(); }
+        if Scanner::is_digit(c) { return self.number(); }
+
+        use TokenType::*;
+
+        match c
+        {
+            '(' => self.make_token(LeftParen),
+            ')' => self.make_token(RightParen),
+            '{' => self.make_token(LeftBrace),
+            '}' => self.make_token(RightBrace),
+            ';' => self.make_token(Semicolon),
+            ',' => self.make_token(Comma),
+            '.' => self.make_token(Dot),
+            '-' => self.make_token(Minus),
+            '+' => self.make_token(Plus),
+            '*' => self.make_token(Star),
+            '!' =>
+            {
+                let type_of = if self.match_char('=') { BangEqual } else { Bang };
+                self.make_token(type_of)
+            }
+            '=' =>
+            {
+                let type_of = if self.match_char('=') { EqualEqual } else { Equal };
+                self.make_token(type_of)
+            }
+            '<' =>
+            {
+                let type_of = if self.match_char('=') { LessEqual } else { Less };
+                self.make_token(type_of)
+            }
+            '>' =>
+            {
+                let type_of = if self.match_char('=') { GreaterEqual } else { Greater };
+                self.make_token(type_of)
+            }
+            '/' =>
+            {
+                if !self.match_char('/')
+                {
+                    return self.make_token(Slash);
+                }
+
+                // Line comment
+                while self.peek() != '\n' && !self.is_at_end()
+                {
+                    self.advance();
+                }
+
+                self.make_token(Comment)
+            }
+
+            '"' => self.string(),
+
+            _ => self.error_token(format!("Unexpected character '{}'.", c)),
+        }
+    }
+
+    fn skip_whitespace(&mut self)
+    {
+        while matches!(self.peek(), ' ' | '\r' | '\t' | '\n')
+        {
+            self.advance();
+        }
+    }
+
+    // The token keeps the escapes as written, unescape gives the value
+    fn string(&mut self) -> Token
+    {
+        while self.peek() != '"' && !self.is_at_end()
+        {
+            // Step over whatever is escaped, so \" doesn't end the string
+            if self.advance() == '\\' && !self.is_at_end()
+            {
+                self.advance();
+            }
+        }
+
+        if self.is_at_end() { return self.incomplete_token("Unterminated string.".to_string()); }
+
+        // The closing "
+        self.advance();
+
+        match unescape(&self.source[self.start + 1..self.current - 1])
+        {
+            Ok(_) => self.make_token(TokenType::String),
+            Err(message) => self.error_token(message),
+        }
+    }
+
+    fn number(&mut self) -> Token
+    {
+        while Scanner::is_digit(self.peek()) { self.advance(); }
+
+        if self.peek() == '.' && Scanner::is_digit(self.peek_next())
+        {
+            self.advance();
+
+            while Scanner::is_digit(self.peek()) { self.advance(); }
+        }
+
+        self.make_token(TokenType::Number)
+    }
+
+    fn identifier(&mut self) -> Token
+    {
+        while Scanner::is_alphanumeric(self.peek()) { self.advance(); }
+
+        let type_of = keywords::keyword(&self.source[self.start..self.current]).unwrap_or(TokenType::Identifier);
+        self.make_token(type_of)
+    }
+
+    fn peek(&self) -> char
+    {
+        self.source[self.current..].chars().next().unwrap_or('\0')
+    }
+
+    fn peek_next(&self) -> char
+    {
+        let mut chars = self.source[self.current..].chars();
+        chars.next();
+        chars.next().unwrap_or('\0')
+    }
+
+    fn match_char(&mut self, expected: char) -> bool
+    {
+        if self.is_at_end() || self.peek() != expected
+        {
+            return false;
+        }
+
+        self.advance();
+        true
+    }
+
+    fn advance(&mut self) -> char
+    {
+        let c = self.peek();
+        self.current += c.len_utf8();
+
+        if c == '\n'
+        {
+            self.line += 1;
+            self.column = 0;
+        }
+        else
+        {
+            self.column += 1;
+        }
+
+        c
+    }
+
+    fn is_at_end(&self) -> bool
+    {
+        self.current >= self.source.len()
+    }
+
+    fn is_digit(character: char) -> bool
+    {
+        character.is_ascii_digit()
+    }
+
+    // Identifiers may use any Unicode letter, not just ASCII
+    fn is_alpha(character: char) -> bool
+    {
+        character.is_alphabetic() || character == '_'
+    }
+
+    fn is_alphanumeric(character: char) -> bool
+    {
+        Scanner::is_alpha(character) || character.is_numeric()
+    }
+
+    fn make_token(&self, type_of: TokenType) -> Token
+    {
+        Token
+        {
+            type_of,
+            span: Span { start: self.start, end: self.current },
+            line: self.start_line,
+            column: self.start_column,
+        }
+    }
+
+    // Error tokens span the offending source, the message is kept by the scanner
+    fn error_token(&mut self, message: String) -> Token
+    {
+        self.error_message = message;
+        self.error_incomplete = false;
+        self.make_token(TokenType::Error)
+    }
+
+    fn incomplete_token(&mut self, message: String) -> Token
+    {
+        let token = self.error_token(message);
+        self.error_incomplete = true;
+        token
+    }
+}
+
+// Resolves the escapes in the text between a string's quotes:
+// \n \t \r \0 \" \\ and \u{...} with one to six hex digits
+pub fn unescape(text: &str) -> Result<String, String>
+{
+    let mut value = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next()
+    {
+        if c != '\\'
+        {
+            value.push(c);
+            continue;
+        }
+
+        let escaped = match chars.next()
+        {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('0') => '\0',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('u') => unicode_escape(&mut chars)?,
+            Some(c) => return Err(format!("Invalid escape sequence '\\{}'.", c)),
+            None => return Err("Unterminated string.".to_string()),
+        };
+
+        value.push(escaped);
+    }
+
+    Ok(value)
+}
+
+// Called just after the 'u'
+fn unicode_escape(chars: &mut Peekable<Chars>) -> Result<char, String>
+{
+    if chars.next_if_eq(&'{').is_none()
+    {
+        return Err("Expect '{' after '\\u'.".to_string());
+    }
+
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_hexdigit())
+    {
+        digits.push(c);
+    }
+
+    if chars.next_if_eq(&'}').is_none()
+    {
+        return Err("Expect '}' to close unicode escape.".to_string());
+    }
+
+    if digits.is_empty() || digits.len() > 6
+    {
+        return Err("Unicode escape must have one to six hex digits.".to_string());
+    }
+
+    u32::from_str_radix(&digits, 16).ok()
+        .and_then(char::from_u32)
+        .ok_or(format!("'{}' is not a valid unicode character.", digits))
+}
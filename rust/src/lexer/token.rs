@@ -0,0 +1,56 @@
+// The order matters, the bytecode compiler indexes its parse rules by it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenType
+{
+    // Single-character tokens.
+    LeftParen, RightParen,
+    LeftBrace, RightBrace,
+    Comma, Dot, Minus, Plus,
+    Semicolon, Slash, Star,
+
+    // One or two character tokens.
+    Bang, BangEqual,
+    Equal, EqualEqual,
+    Greater, GreaterEqual,
+    Less, LessEqual,
+
+    // Literals.
+    Identifier, String, Number,
+
+    // Keywords.
+    And, Class, Else, False,
+    For, Func, If, Null, Or,
+    Print, Return, Super, This,
+    True, Var, While,
+
+    // Trivia, which the parsers skip over.
+    Comment,
+
+    Error, EOF
+}
+
+// Byte offsets of a token's text in the source
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Span
+{
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Token
+{
+    pub type_of: TokenType,
+    pub span: Span,
+    pub line: usize,
+    // Counted in characters from 1, not bytes
+    pub column: usize,
+}
+
+impl Token
+{
+    pub fn lexeme<'a>(&self, source: &'a str) -> &'a str
+    {
+        &source[self.span.start..self.span.end]
+    }
+}
@@ -1,8 +1,8 @@
-#![feature(fn_traits)]
-
 pub mod webclient;
 pub mod components;
 pub mod physics;
 pub mod animator;
 pub mod ast;
-pub mod bytecode;
\ No newline at end of file
+pub mod bytecode;
+pub mod lexer;
+pub mod diagnostics;
\ No newline at end of file
@@ -1,3 +1,704 @@
-fn main() {
-    println!("Hello, world!");
+/*
+    Rusty Lox
+
+    One binary for both interpreters. Scripts run on the bytecode VM unless
+    --engine ast picks the tree-walker. Without a subcommand it starts the
+    REPL, which keeps one VM for the whole session so globals carry over
+    between inputs. Input that stops part way through, like an open block,
+    asks for more before it runs. Lines starting with ':' are commands, see
+    :help.
+
+    Exit codes follow sysexits.h, as clox does.
+*/
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use rustyline::{error::ReadlineError, DefaultEditor};
+
+use one_hundred_days_of_code::{
+    ast::{
+        error::ErrorHandler,
+        formatter,
+        interpreter::Interpreter,
+        parser::Parser,
+        resolver::Resolver,
+        scanner::Scanner,
+        stmt::Stmt,
+    },
+    bytecode::{
+        chunk::Chunk,
+        compiler::CompileError,
+        debug,
+        serialize::MAGIC,
+        vm::{InterpretResult, RuntimeError, VM},
+    },
+    lexer::{self, token::TokenType},
+};
+
+const EX_USAGE: i32 = 64;
+const EX_DATAERR: i32 = 65;
+const EX_SOFTWARE: i32 = 70;
+const EX_IOERR: i32 = 74;
+
+const HISTORY_FILE: &str = ".rlox_history";
+
+const HELP: &str = "\
+:dis [code]   Disassemble code, or the last input that compiled
+:stack        Show what is left on the stack, ie. after a runtime error
+:globals      List every global and its value
+:load <file>  Run a file in this session
+:help         Show this
+:quit         Leave, as does Ctrl-D";
+
+fn report_errors(errors: &[CompileError], source: &str)
+{
+    for error in errors
+    {
+        eprintln!("{}\n{}", error, error.excerpt(source));
+    }
+}
+
+// Bytecode files carry no source, so there may be nothing to point at
+fn report_runtime_error(error: &RuntimeError, source: Option<&str>)
+{
+    eprintln!("{}", error.message);
+
+    if let Some(source) = source
+    {
+        eprintln!("{}", error.excerpt(source));
+    }
+
+    for frame in &error.trace
+    {
+        eprintln!("{}", frame);
+    }
+}
+
+// Only the first error counts, the rest may just follow on from it
+fn is_incomplete(errors: &[CompileError]) -> bool
+{
+    errors.first().is_some_and(|error| error.incomplete)
+}
+
+// Kept in the home directory, or the working directory without one
+fn history_path() -> PathBuf
+{
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map_or_else(PathBuf::new, PathBuf::from)
+        .join(HISTORY_FILE)
+}
+
+struct Repl
+{
+    vm: VM,
+    // The last input that compiled, for :dis
+    last_source: Option<String>,
+    // Every input that compiled, in order. Inputs are numbered on from the
+    // lines before them, so an error in a function from an earlier input
+    // can still be pointed at
+    transcript: String,
+    lines: usize,
+}
+
+impl Repl
+{
+    fn new() -> Repl
+    {
+        let mut vm = VM::new();
+        vm.init();
+
+        Repl { vm, last_source: None, transcript: String::new(), lines: 0 }
+    }
+
+    fn free(&mut self)
+    {
+        self.vm.free();
+    }
+
+    fn run(&mut self) -> Result<(), String>
+    {
+        let mut editor = DefaultEditor::new().map_err(|e| format!("Error: {}", e))?;
+
+        // There is no history the first time round
+        let history = history_path();
+        let _ = editor.load_history(&history);
+
+        println!("=== Rusty Lox Repl ===");
+        println!("Type :help for commands");
+
+        let mut buffer = String::new();
+
+        loop
+        {
+            let prompt = if buffer.is_empty() { "> " } else { "... " };
+
+            let line = match editor.readline(prompt)
+            {
+                Ok(line) => line,
+                // Ctrl-C throws away the unfinished input
+                Err(ReadlineError::Interrupted) =>
+                {
+                    buffer.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) =>
+                {
+                    // Whatever was left unfinished is reported rather than dropped
+                    if !buffer.is_empty()
+                    {
+                        self.submit(&buffer, true);
+                    }
+                    break;
+                }
+                Err(e) => return Err(format!("Error: {}", e)),
+            };
+
+            if buffer.is_empty()
+            {
+                let trimmed = line.trim();
+
+                if trimmed.is_empty()
+                {
+                    continue;
+                }
+
+                if let Some(command) = trimmed.strip_prefix(':')
+                {
+                    let _ = editor.add_history_entry(trimmed);
+
+                    if !self.command(command)
+                    {
+                        break;
+                    }
+
+                    continue;
+                }
+            }
+
+            // A blank line gives up waiting, so the errors are shown
+            let force = !buffer.is_empty() && line.trim().is_empty();
+
+            buffer.push_str(&line);
+            buffer.push('\n');
+
+            if self.submit(&buffer, force)
+            {
+                let _ = editor.add_history_entry(buffer.trim_end());
+                buffer.clear();
+            }
+        }
+
+        editor.save_history(&history).map_err(|e| format!("Error: {}", e))
+    }
+
+    // Runs the input once it's complete. Returns false while it needs more
+    fn submit(&mut self, source: &str, force: bool) -> bool
+    {
+        // Blank lines standing in for the earlier inputs
+        let numbered = format!("{}{}", "\n".repeat(self.lines), source);
+
+        match self.vm.compile(numbered.clone())
+        {
+            Ok(chunk) =>
+            {
+                self.last_source = Some(source.to_string());
+
+                self.transcript.push_str(source);
+                if !source.ends_with('\n')
+                {
+                    self.transcript.push('\n');
+                }
+                self.lines = self.transcript.lines().count();
+
+                if let InterpretResult::RuntimeError(error) = self.vm.interpret_chunk(chunk)
+                {
+                    report_runtime_error(&error, Some(&self.transcript));
+                }
+
+                true
+            }
+            Err(errors) if !force && is_incomplete(&errors) => false,
+            Err(errors) =>
+            {
+                report_errors(&errors, &numbered);
+                true
+            }
+        }
+    }
+
+    // Returns false to quit
+    fn command(&mut self, command: &str) -> bool
+    {
+        let (name, argument) = match command.split_once(char::is_whitespace)
+        {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (command, ""),
+        };
+
+        match name
+        {
+            "dis" => self.disassemble(argument),
+            "stack" => self.stack(),
+            "globals" => self.globals(),
+            "load" => self.load(argument),
+            "help" => println!("{}", HELP),
+            "quit" => return false,
+            _ => eprintln!("Unknown command ':{}', try :help.", name),
+        }
+
+        true
+    }
+
+    fn disassemble(&mut self, code: &str)
+    {
+        let source = match (code, &self.last_source)
+        {
+            ("", Some(source)) => source.clone(),
+            ("", None) => return eprintln!("Nothing to disassemble yet."),
+            (code, _) => code.to_string(),
+        };
+
+        // Compiled again rather than kept, the chunk is consumed by running it
+        match self.vm.compile(source.clone())
+        {
+            Ok(chunk) => debug::disassemble_all(&chunk, "<script>".to_string()),
+            Err(errors) => report_errors(&errors, &source),
+        }
+    }
+
+    fn stack(&self)
+    {
+        let stack = self.vm.stack();
+
+        if stack.is_empty()
+        {
+            return println!("Stack is empty.");
+        }
+
+        // Bottom of the stack first
+        for (slot, value) in stack.iter().enumerate()
+        {
+            println!("{:4} {}", slot, value);
+        }
+    }
+
+    fn globals(&self)
+    {
+        for (name, value) in self.vm.globals()
+        {
+            println!("{} = {}", name, value);
+        }
+    }
+
+    fn load(&mut self, path: &str)
+    {
+        if path.is_empty()
+        {
+            return eprintln!("Usage: :load <file>");
+        }
+
+        match fs::read_to_string(path)
+        {
+            Ok(source) =>
+            {
+                self.submit(&source, true);
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Engine
+{
+    Ast,
+    Bytecode,
+}
+
+// Why the program stopped early. Compile and runtime errors have already
+// been reported by the time they get here
+enum Failure
+{
+    Usage(String),
+    Invalid(String),
+    Compile,
+    Runtime,
+    Io(String),
+    // fmt --check found a file that would change
+    Unformatted(String),
+}
+
+impl Failure
+{
+    fn exit_code(&self) -> i32
+    {
+        use Failure::*;
+
+        match self
+        {
+            Usage(_) => EX_USAGE,
+            Invalid(_) | Compile => EX_DATAERR,
+            Runtime => EX_SOFTWARE,
+            Io(_) => EX_IOERR,
+            // The same as rustfmt --check
+            Unformatted(_) => 1,
+        }
+    }
+}
+
+fn read_source(path: &str) -> Result<String, Failure>
+{
+    fs::read_to_string(path).map_err(|e| Failure::Io(format!("Error: {}: {}", path, e)))
+}
+
+// Compiled bytecode files are taken as they are, anything else is compiled from source
+fn load_chunk(path: &str, vm: &mut VM) -> Result<(Chunk, Option<String>), Failure>
+{
+    let bytes = fs::read(path).map_err(|e| Failure::Io(format!("Error: {}: {}", path, e)))?;
+
+    if bytes.starts_with(MAGIC)
+    {
+        let chunk = Chunk::read_from(&mut bytes.as_slice(), vm)
+            .map_err(|e| Failure::Invalid(format!("Error: {}: {}", path, e)))?;
+
+        return Ok((chunk, None));
+    }
+
+    let source = String::from_utf8(bytes).map_err(|e| Failure::Invalid(format!("Error: {}: {}", path, e)))?;
+
+    match vm.compile(source.clone())
+    {
+        Ok(chunk) => Ok((chunk, Some(source))),
+        Err(errors) =>
+        {
+            report_errors(&errors, &source);
+            Err(Failure::Compile)
+        }
+    }
+}
+
+// Scans, parses and resolves, reporting anything wrong on the way
+fn parse_ast(source: String, errors: &mut ErrorHandler) -> Result<Vec<Stmt>, Failure>
+{
+    let mut scanner = Scanner::with_source(source);
+    let tokens = match scanner.scan_tokens()
+    {
+        Ok(tokens) => tokens.clone(),
+        Err(message) =>
+        {
+            eprintln!("{}", message);
+            return Err(Failure::Compile);
+        }
+    };
+
+    let statements = Parser::new(tokens, errors).parse();
+
+    if !errors.had_error()
+    {
+        Resolver::new(errors).resolve(&statements);
+    }
+
+    match errors.had_error()
+    {
+        true => Err(Failure::Compile),
+        false => Ok(statements),
+    }
+}
+
+// The script's own path comes first in what it sees, like argv in C
+fn run_file(engine: Engine, path: &str, args: Vec<String>) -> Result<(), Failure>
+{
+    let mut script_args = vec![path.to_string()];
+    script_args.extend(args);
+
+    match engine
+    {
+        Engine::Bytecode =>
+        {
+            let mut vm = VM::new();
+            vm.init();
+            vm.set_args(script_args);
+
+            let result = load_chunk(path, &mut vm).and_then(|(chunk, source)|
+            {
+                match vm.interpret_chunk(chunk)
+                {
+                    InterpretResult::RuntimeError(error) =>
+                    {
+                        report_runtime_error(&error, source.as_deref());
+                        Err(Failure::Runtime)
+                    }
+                    _ => Ok(()),
+                }
+            });
+
+            vm.free();
+            result
+        }
+        Engine::Ast =>
+        {
+            let mut errors = ErrorHandler::new();
+            let statements = parse_ast(read_source(path)?, &mut errors)?;
+
+            let mut interpreter = Interpreter::new();
+            interpreter.set_args(script_args);
+            interpreter.interpret(&statements, &mut errors);
+
+            match errors.had_runtime_error()
+            {
+                true => Err(Failure::Runtime),
+                false => Ok(()),
+            }
+        }
+    }
+}
+
+fn compile_file(path: &str, out_path: &str) -> Result<(), Failure>
+{
+    let mut vm = VM::new();
+
+    let source = read_source(path)?;
+    let result = match vm.compile(source.clone())
+    {
+        Ok(chunk) => match fs::File::create(out_path)
+        {
+            Ok(mut file) => chunk.write_to(&mut file).map_err(|e| Failure::Io(format!("Error: {}: {}", out_path, e))),
+            Err(e) => Err(Failure::Io(format!("Error: {}: {}", out_path, e))),
+        },
+        Err(errors) =>
+        {
+            report_errors(&errors, &source);
+            Err(Failure::Compile)
+        }
+    };
+
+    vm.free();
+    result
+}
+
+fn disassemble_file(path: &str) -> Result<(), Failure>
+{
+    let mut vm = VM::new();
+
+    let result = load_chunk(path, &mut vm).map(|(chunk, _)| debug::disassemble_all(&chunk, path.to_string()));
+
+    vm.free();
+    result
+}
+
+// Every token the lexer sees, comments included
+fn print_tokens(path: &str) -> Result<(), Failure>
+{
+    let mut scanner = lexer::scanner::Scanner::new();
+    scanner.init(read_source(path)?);
+
+    let mut had_error = false;
+
+    loop
+    {
+        let token = scanner.scan_token();
+        let position = format!("{}:{}", token.line, token.column);
+
+        match token.type_of
+        {
+            TokenType::Error =>
+            {
+                had_error = true;
+                println!("{:<8} {:<14} {}", position, "Error", scanner.error_message());
+            }
+            TokenType::EOF =>
+            {
+                println!("{:<8} EOF", position);
+                break;
+            }
+            type_of => println!("{:<8} {:<14} {}", position, format!("{:?}", type_of), scanner.lexeme(&token)),
+        }
+    }
+
+    match had_error
+    {
+        true => Err(Failure::Compile),
+        false => Ok(()),
+    }
+}
+
+// Reports errors without running anything
+fn check_file(engine: Engine, path: &str) -> Result<(), Failure>
+{
+    let source = read_source(path)?;
+
+    match engine
+    {
+        Engine::Bytecode =>
+        {
+            let mut vm = VM::new();
+            let result = vm.compile(source.clone());
+            vm.free();
+
+            result.map(|_| ()).map_err(|errors|
+            {
+                report_errors(&errors, &source);
+                Failure::Compile
+            })
+        }
+        Engine::Ast => parse_ast(source, &mut ErrorHandler::new()).map(|_| ()),
+    }
+}
+
+// Prints the script in the canonical style, or with check only says if it already is
+fn format_file(path: &str, check: bool) -> Result<(), Failure>
+{
+    let source = read_source(path)?;
+    let formatted = formatter::format(&source).map_err(Failure::Invalid)?;
+
+    match check
+    {
+        true if formatted != source => Err(Failure::Unformatted(format!("{} is not formatted.", path))),
+        true => Ok(()),
+        false =>
+        {
+            print!("{}", formatted);
+            Ok(())
+        }
+    }
+}
+
+fn run_repl(engine: Engine) -> Result<(), Failure>
+{
+    if engine == Engine::Ast
+    {
+        return Err(Failure::Usage("The REPL only runs on the bytecode engine.".to_string()));
+    }
+
+    let mut repl = Repl::new();
+    let result = repl.run();
+    repl.free();
+
+    result.map_err(Failure::Io)
+}
+
+fn bytecode_only(engine: Engine, command: &str) -> Result<(), Failure>
+{
+    match engine
+    {
+        Engine::Ast => Err(Failure::Usage(format!("'{}' only works with the bytecode engine.", command))),
+        Engine::Bytecode => Ok(()),
+    }
+}
+
+fn app() -> App<'static, 'static>
+{
+    let file = |help| Arg::with_name("file").required(true).help(help);
+
+    App::new("rlox")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Runs Lox scripts, on the bytecode VM or the tree-walking interpreter")
+        .setting(AppSettings::VersionlessSubcommands)
+        .arg(Arg::with_name("engine")
+            .long("engine")
+            .takes_value(true)
+            .possible_values(&["ast", "bytecode"])
+            .default_value("bytecode")
+            .global(true)
+            .help("Which interpreter to use"))
+        .subcommand(SubCommand::with_name("run")
+            .about("Runs a script or a compiled bytecode file")
+            .setting(AppSettings::TrailingVarArg)
+            .arg(file("Script to run"))
+            .arg(Arg::with_name("args")
+                .multiple(true)
+                .allow_hyphen_values(true)
+                .help("Handed to the script, see argc() and argv()")))
+        .subcommand(SubCommand::with_name("repl")
+            .about("Starts an interactive session, the default without a subcommand"))
+        .subcommand(SubCommand::with_name("compile")
+            .about("Compiles a script to a bytecode file")
+            .arg(file("Script to compile"))
+            .arg(Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .takes_value(true)
+                .help("Where to write the bytecode, the script with a .loxc extension by default")))
+        .subcommand(SubCommand::with_name("disasm")
+            .about("Prints the bytecode of a script or compiled file")
+            .arg(file("Script or compiled file")))
+        .subcommand(SubCommand::with_name("tokens")
+            .about("Prints the tokens of a script")
+            .arg(file("Script to scan")))
+        .subcommand(SubCommand::with_name("check")
+            .about("Reports errors in a script without running it")
+            .arg(file("Script to check")))
+        .subcommand(SubCommand::with_name("fmt")
+            .about("Prints a script in the canonical style")
+            .arg(file("Script to format"))
+            .arg(Arg::with_name("check")
+                .long("check")
+                .help("Prints nothing, failing if the script isn't formatted already")))
+}
+
+fn dispatch(matches: &ArgMatches) -> Result<(), Failure>
+{
+    let engine = |matches: &ArgMatches| match matches.value_of("engine")
+    {
+        Some("ast") => Engine::Ast,
+        _ => Engine::Bytecode,
+    };
+
+    match matches.subcommand()
+    {
+        ("run", Some(run)) =>
+        {
+            let args = run.values_of("args").map_or_else(Vec::new, |args| args.map(String::from).collect());
+            run_file(engine(run), run.value_of("file").unwrap(), args)
+        }
+        ("compile", Some(compile)) =>
+        {
+            bytecode_only(engine(compile), "compile")?;
+
+            let path = compile.value_of("file").unwrap();
+            let output = match compile.value_of("output")
+            {
+                Some(output) => PathBuf::from(output),
+                None => Path::new(path).with_extension("loxc"),
+            };
+
+            compile_file(path, &output.to_string_lossy())
+        }
+        ("disasm", Some(disasm)) =>
+        {
+            bytecode_only(engine(disasm), "disasm")?;
+            disassemble_file(disasm.value_of("file").unwrap())
+        }
+        ("tokens", Some(tokens)) => print_tokens(tokens.value_of("file").unwrap()),
+        ("check", Some(check)) => check_file(engine(check), check.value_of("file").unwrap()),
+        ("fmt", Some(fmt)) => format_file(fmt.value_of("file").unwrap(), fmt.is_present("check")),
+        ("repl", Some(repl)) => run_repl(engine(repl)),
+        _ => run_repl(engine(matches)),
+    }
+}
+
+fn main()
+{
+    let matches = app().get_matches();
+
+    if let Err(failure) = dispatch(&matches)
+    {
+        match &failure
+        {
+            Failure::Usage(message) | Failure::Invalid(message) | Failure::Io(message) | Failure::Unformatted(message) =>
+            {
+                eprintln!("{}", message);
+            }
+            Failure::Compile | Failure::Runtime => {},
+        }
+
+        process::exit(failure.exit_code());
+    }
 }
@@ -0,0 +1,183 @@
+mod common;
+
+use common::Rng;
+use one_hundred_days_of_code::ast::{
+    formatter::format,
+    scanner::Scanner,
+    tokens::{Token, TokenType},
+};
+
+fn scan(source: &str) -> Vec<Token>
+{
+    let mut scanner = Scanner::with_source(source.to_string());
+    scanner.scan_tokens().unwrap().clone()
+}
+
+fn comments(source: &str) -> Vec<String>
+{
+    let mut scanner = Scanner::with_source(source.to_string());
+    scanner.scan_tokens().unwrap();
+    scanner.comments().iter().map(|comment| comment.lexeme.clone()).collect()
+}
+
+const CORPUS: &[&str] = &[
+    "print 1;",
+    "let a=1;let b;b=a+2*3-(4/5);print b;",
+    "fn fib(n){if(n<2)return n;return fib(n-1)+fib(n-2);}print fib(10);",
+    "fn counter(){let i=0;fn inc(){i=i+1;return i;}return inc;}let c=counter();c();print c();",
+    "class A{init(n){this.n=n;}get(){return this.n;}}class B<A{get(){return parent.get()*2;}}print B(2).get();",
+    "if(true)print 1;else if(false)print 2;else{print 3;}",
+    "for(let i=0;i<3;i=i+1)print i;for(;;){}for(i=0;;)print i;while(!false and true or null){print -1;}",
+    "{let x=\"a\\tb\\n\\\"c\\\"\\\\\";{print x;}}",
+    "a.b.c=d.e(f,g)(h);print this_is_fine==null!=true>=1<=2>3<4;",
+    "// only a comment",
+    "",
+    "let a = 1; // one\n\n\n// two\nfn f() // three\n{ // four\n  // five\n  return; // six\n  // seven\n}\n// eight\n",
+];
+
+#[test]
+fn formats_in_allman_style()
+{
+    let formatted = format("class A < B{m(a,b){if(a)return b;else{print a+b;}}}fn f(){while(x)x=x-1;}").unwrap();
+
+    assert_eq!(formatted, "\
+class A < B
+{
+    m(a, b)
+    {
+        if (a)
+            return b;
+        else
+        {
+            print a + b;
+        }
+    }
+}
+fn f()
+{
+    while (x)
+        x = x - 1;
+}
+");
+}
+
+#[test]
+fn keeps_comments_where_they_were()
+{
+    let source = "// head\nlet a = 1;   // trailing\n{ // open\n    print a;\n    // end of block\n}\n// tail";
+    let formatted = format(source).unwrap();
+
+    assert_eq!(formatted, "\
+// head
+let a = 1; // trailing
+{ // open
+    print a;
+    // end of block
+}
+// tail
+");
+}
+
+#[test]
+fn comments_inside_class_bodies()
+{
+    let formatted = format("class A\n{\n    // first\n    m() {}\n\n    // second\n    n() {} // after\n}").unwrap();
+
+    assert_eq!(formatted, "\
+class A
+{
+    // first
+    m()
+    {
+    }
+
+    // second
+    n()
+    {
+    } // after
+}
+");
+}
+
+#[test]
+fn keeps_at_most_one_blank_line()
+{
+    let formatted = format("print 1;\n\n\n\nprint 2;\nprint 3;\n{\n\n  print 4;\n\n}").unwrap();
+
+    assert_eq!(formatted, "print 1;\n\nprint 2;\nprint 3;\n{\n    print 4;\n}\n");
+}
+
+#[test]
+fn strings_keep_their_value()
+{
+    let source = "print \"tab\\there \\\"q\\\" \\u{41} 東京\nnext line\";";
+    let formatted = format(source).unwrap();
+
+    assert_eq!(formatted, "print \"tab\\there \\\"q\\\" A 東京\\nnext line\";\n");
+    assert_eq!(scan(&formatted)[1].literal, scan(source)[1].literal);
+}
+
+#[test]
+fn numbers_are_written_canonically()
+{
+    assert_eq!(format("print 1.50 + 007 - 2.0;").unwrap(), "print 1.5 + 7 - 2;\n");
+}
+
+#[test]
+fn formatting_twice_changes_nothing()
+{
+    for source in CORPUS
+    {
+        let once = format(source).unwrap();
+        let twice = format(&once).unwrap();
+
+        assert_eq!(once, twice, "not idempotent for {:?}", source);
+    }
+}
+
+#[test]
+fn tokens_and_comments_survive()
+{
+    for source in CORPUS
+    {
+        let formatted = format(source).unwrap();
+
+        let before: Vec<TokenType> = scan(source).iter().map(|token| token.type_of).collect();
+        let after: Vec<TokenType> = scan(&formatted).iter().map(|token| token.type_of).collect();
+
+        assert_eq!(before, after, "{:?}", source);
+        assert_eq!(comments(source), comments(&formatted), "{:?}", source);
+    }
+}
+
+#[test]
+fn parse_errors_are_not_formatted()
+{
+    assert!(format("print ;").is_err());
+    assert!(format("let = 1;").is_err());
+    assert!(format("print \"open").is_err());
+}
+
+#[test]
+fn whitespace_does_not_change_the_output()
+{
+    const SPACING: &[&str] = &[" ", "  ", "\n", "\t", " \n ", "\r\n"];
+
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    // Comments aren't tokens, so these sources are code only
+    for source in CORPUS.iter().filter(|source| comments(source).is_empty())
+    {
+        let lexemes: Vec<String> = scan(source).iter().map(|token| token.lexeme.clone()).collect();
+        let expected = format(&lexemes.join(" ")).unwrap();
+
+        for _ in 0..20
+        {
+            let respaced: String = lexemes.iter()
+                .map(|lexeme| format!("{}{}", lexeme, rng.pick(SPACING)))
+                .collect();
+
+            assert_eq!(format(&respaced).unwrap(), expected, "{:?}", respaced);
+        }
+    }
+}
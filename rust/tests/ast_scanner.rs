@@ -0,0 +1,210 @@
+mod common;
+
+use common::Rng;
+use one_hundred_days_of_code::ast::{
+    scanner::Scanner,
+    tokens::{Token, TokenType},
+};
+
+fn scan(source: &str) -> Result<Vec<Token>, String>
+{
+    let mut scanner = Scanner::with_source(source.to_string());
+    scanner.scan_tokens().cloned()
+}
+
+fn types(source: &str) -> Vec<TokenType>
+{
+    scan(source).unwrap().iter().map(|token| token.type_of).collect()
+}
+
+// Byte offset of a 1-based line and character column
+fn offset_of(source: &str, line: i32, column: i32) -> Option<usize>
+{
+    let mut current = (1, 1);
+
+    for (offset, c) in source.char_indices()
+    {
+        if current == (line, column) { return Some(offset); }
+
+        current = match c
+        {
+            '\n' => (current.0 + 1, 1),
+            _ => (current.0, current.1 + 1),
+        };
+    }
+
+    if current == (line, column) { Some(source.len()) } else { None }
+}
+
+// Every token must sit exactly where its line and column say it does
+fn check_positions(source: &str, tokens: &[Token])
+{
+    for token in tokens
+    {
+        let offset = offset_of(source, token.line, token.column)
+            .unwrap_or_else(|| panic!("{:?} points outside of {:?}", token, source));
+
+        assert!(source[offset..].starts_with(&token.lexeme), "{:?} is not at its position in {:?}", token, source);
+    }
+}
+
+#[test]
+fn keywords_and_operators()
+{
+    use TokenType::*;
+
+    assert_eq!(
+        types("class fn let if else while for return print and or this parent true false null"),
+        vec![Class, Func, Var, If, Else, While, For, Return, Print, And, Or, This, Super, True, False, Null, EOF]);
+
+    assert_eq!(
+        types("( ) { } , . - + ; / * ! != = == > >= < <="),
+        vec![LeftParen, RightParen, LeftBrace, RightBrace, Comma, Dot, Minus, Plus, Semicolon, Slash, Star,
+            Bang, BangEqual, Equal, EqualEqual, Greater, GreaterEqual, Less, LessEqual, EOF]);
+}
+
+#[test]
+fn line_comments()
+{
+    use TokenType::*;
+
+    assert_eq!(types("a // b c\nd"), vec![Identifier, Identifier, EOF]);
+    assert_eq!(types("a / b"), vec![Identifier, Slash, Identifier, EOF]);
+    assert_eq!(types("a /= b"), vec![Identifier, Slash, Equal, Identifier, EOF]);
+    assert_eq!(types("// only a comment"), vec![EOF]);
+}
+
+#[test]
+fn numbers()
+{
+    let tokens = scan("12 3.25 4. .5").unwrap();
+    let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+
+    assert_eq!(lexemes, vec!["12", "3.25", "4", ".", ".", "5", ""]);
+    assert_eq!(tokens[1].literal, "3.25");
+}
+
+#[test]
+fn unicode_identifiers()
+{
+    let tokens = scan("let naïve = café + 東京 + ñ_2;").unwrap();
+    let identifiers: Vec<&str> = tokens.iter()
+        .filter(|token| token.type_of == TokenType::Identifier)
+        .map(|token| token.lexeme.as_str())
+        .collect();
+
+    assert_eq!(identifiers, vec!["naïve", "café", "東京", "ñ_2"]);
+}
+
+#[test]
+fn non_ascii_strings_are_sliced_correctly()
+{
+    let tokens = scan("print \"héllo 🌍\" + \"ü\";").unwrap();
+
+    assert_eq!(tokens[1].lexeme, "\"héllo 🌍\"");
+    assert_eq!(tokens[1].literal, "héllo 🌍");
+    assert_eq!(tokens[3].literal, "ü");
+}
+
+#[test]
+fn escape_sequences()
+{
+    let tokens = scan(r#""a\nb\tc\"d\\e\r\0" "\u{41}\u{e9}\u{1F600}""#).unwrap();
+
+    assert_eq!(tokens[0].literal, "a\nb\tc\"d\\e\r\0");
+    assert_eq!(tokens[1].literal, "Aé😀");
+    // The lexeme keeps the escapes as written
+    assert_eq!(tokens[1].lexeme, r#""\u{41}\u{e9}\u{1F600}""#);
+}
+
+#[test]
+fn bad_escape_sequences()
+{
+    for source in [r#""\q""#, r#""\u41""#, r#""\u{}""#, r#""\u{1234567}""#, r#""\u{D800}""#, r#""\u{41""#]
+    {
+        assert!(scan(source).is_err(), "accepted {}", source);
+    }
+}
+
+#[test]
+fn columns_count_characters()
+{
+    let source = "let é = \"ü\";\n\t  x;";
+    let tokens = scan(source).unwrap();
+
+    let positions: Vec<(i32, i32)> = tokens.iter().map(|token| (token.line, token.column)).collect();
+    assert_eq!(positions, vec![(1, 1), (1, 5), (1, 7), (1, 9), (1, 12), (2, 4), (2, 5), (2, 6)]);
+    check_positions(source, &tokens);
+}
+
+#[test]
+fn multiline_strings_start_where_they_open()
+{
+    let tokens = scan("a \"one\ntwo\" b").unwrap();
+
+    assert_eq!((tokens[1].line, tokens[1].column), (1, 3));
+    assert_eq!(tokens[1].literal, "one\ntwo");
+    assert_eq!((tokens[2].line, tokens[2].column), (2, 6));
+}
+
+#[test]
+fn reports_every_error_with_its_position()
+{
+    let error = scan("a # b\nc \"\\q\" @\n\"open").unwrap_err();
+    let lines: Vec<&str> = error.lines().collect();
+
+    assert_eq!(lines, vec![
+        "[line 1:3] Error: Unexpected character '#'.",
+        "[line 2:3] Error: Invalid escape sequence '\\q'.",
+        "[line 2:8] Error: Unexpected character '@'.",
+        "[line 3:1] Error: Unterminated string.",
+    ]);
+}
+
+const PIECES: &[&str] = &[
+    "a", "z9", "_", "é", "東", "🌍", "0", "12", "3.5", ".", " ", "\t", "\n", "\r\n",
+    "\"", "\\", "\\n", "\\u{", "}", "{", "(", ")", "=", "!", "<", ">", "/", "//", "*", "+", "-", ";", ",",
+    "let", "fn", "parent", "#", "\0", "\u{200B}",
+];
+
+#[test]
+fn fuzz_never_panics_and_positions_hold()
+{
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    for _ in 0..2000
+    {
+        let length = rng.next() % 40;
+        let source: String = (0..length).map(|_| rng.pick(PIECES)).collect();
+
+        if let Ok(tokens) = scan(&source)
+        {
+            check_positions(&source, &tokens);
+            assert_eq!(tokens.last().map(|token| token.type_of), Some(TokenType::EOF));
+        }
+    }
+}
+
+#[test]
+fn fuzz_rescanning_lexemes_is_stable()
+{
+    // No quotes, backslashes or comments, so every lexeme stands alone
+    const CODE: &[&str] = &["a", "é", "東京", "12", "3.5", "(", ")", "{", "}", "=", "==", "!", "!=", "<=", ">", "+", "-", "*", ";", ",", "let", "fn", "or"];
+
+    let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+
+    for _ in 0..500
+    {
+        let length = 1 + rng.next() % 30;
+        let pieces: Vec<&str> = (0..length).map(|_| rng.pick(CODE)).collect();
+        let source = pieces.join(" ");
+
+        let tokens = scan(&source).unwrap();
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+        let rescanned = scan(&lexemes.join(" ")).unwrap();
+
+        let original: Vec<TokenType> = tokens.iter().map(|token| token.type_of).collect();
+        let again: Vec<TokenType> = rescanned.iter().map(|token| token.type_of).collect();
+        assert_eq!(original, again, "{:?}", source);
+    }
+}
@@ -0,0 +1,364 @@
+use one_hundred_days_of_code::bytecode::{
+    chunk::{Chunk, OpCode},
+    serialize::{ChunkError, MAGIC, VERSION},
+    vm::{InterpretResult, VM},
+};
+
+const PROGRAM: &str = "
+class A { init(n) { this.n = n; } get() { return this.n; } }
+class B < A { get() { return parent.get() * 2; } }
+
+fn counter()
+{
+    let count = 0;
+    fn increment() { count = count + 1; return count; }
+    return increment;
+}
+
+let next = counter();
+next();
+let total = B(21).get() + next();
+if (total != 44) { print missing; }
+
+let s = \"\";
+for (let i = 0; i < 3; i = i + 1) { s = s + \"ab\"; }
+let flags = !true or false and null;
+print s;
+";
+
+fn compile(vm: &mut VM, source: &str) -> Chunk
+{
+    match vm.compile(source.to_string())
+    {
+        Ok(chunk) => chunk,
+        Err(errors) => panic!("failed to compile: {:?}", errors),
+    }
+}
+
+fn to_bytes(chunk: &Chunk) -> Vec<u8>
+{
+    let mut bytes = Vec::new();
+    chunk.write_to(&mut bytes).unwrap();
+    bytes
+}
+
+fn load(vm: &mut VM, bytes: &[u8]) -> Result<Chunk, ChunkError>
+{
+    Chunk::read_from(&mut &bytes[..], vm)
+}
+
+// A file holding a single hand written chunk with no constants
+fn raw_file(code: &[u8]) -> Vec<u8>
+{
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(code.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(code);
+
+    // One run covering all of the code, at line 1 column 1
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    for field in [0u32, 1, 1]
+    {
+        bytes.extend_from_slice(&field.to_le_bytes());
+    }
+
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn round_trip_is_stable()
+{
+    let mut vm = VM::new();
+    let chunk = compile(&mut vm, PROGRAM);
+    let bytes = to_bytes(&chunk);
+
+    assert!(bytes.starts_with(MAGIC));
+
+    let loaded = load(&mut vm, &bytes).unwrap();
+    assert_eq!(to_bytes(&loaded), bytes);
+}
+
+#[test]
+fn loaded_chunk_runs_in_a_fresh_vm()
+{
+    let bytes = to_bytes(&compile(&mut VM::new(), PROGRAM));
+
+    let mut vm = VM::new();
+    let chunk = load(&mut vm, &bytes).unwrap();
+    assert!(matches!(vm.interpret_chunk(chunk), InterpretResult::Okay));
+}
+
+#[test]
+fn loaded_chunk_keeps_lines_for_runtime_errors()
+{
+    let source = "fn fail()\n{\n    return -\"oops\";\n}\nfail();\n";
+    let bytes = to_bytes(&compile(&mut VM::new(), source));
+
+    let mut vm = VM::new();
+    let chunk = load(&mut vm, &bytes).unwrap();
+
+    match vm.interpret_chunk(chunk)
+    {
+        InterpretResult::RuntimeError(error) =>
+        {
+            assert_eq!(error.line, 3);
+            assert_eq!(error.column, 12);
+            assert_eq!(error.trace.len(), 2);
+            assert_eq!(error.trace[0].function.as_deref(), Some("fail"));
+            assert_eq!(error.trace[1].line, 5);
+        }
+        _ => panic!("expected a runtime error"),
+    }
+}
+
+#[test]
+fn rejects_bad_magic()
+{
+    let mut bytes = to_bytes(&compile(&mut VM::new(), "print 1;"));
+    bytes[0] = b'X';
+
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::BadMagic)));
+}
+
+#[test]
+fn rejects_unsupported_version()
+{
+    let mut bytes = to_bytes(&compile(&mut VM::new(), "print 1;"));
+    bytes[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+
+    match load(&mut VM::new(), &bytes)
+    {
+        Err(ChunkError::UnsupportedVersion(version)) => assert_eq!(version, VERSION + 1),
+        _ => panic!("expected an unsupported version error"),
+    }
+}
+
+#[test]
+fn rejects_every_truncation()
+{
+    let bytes = to_bytes(&compile(&mut VM::new(), PROGRAM));
+
+    for length in 0..bytes.len()
+    {
+        let result = load(&mut VM::new(), &bytes[..length]);
+        assert!(matches!(result, Err(ChunkError::Truncated)), "accepted a file cut at {} bytes", length);
+    }
+}
+
+#[test]
+fn rejects_trailing_bytes()
+{
+    let mut bytes = to_bytes(&compile(&mut VM::new(), "print 1;"));
+    bytes.push(0);
+
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_unknown_opcode()
+{
+    let bytes = raw_file(&[0xff, u8::from(OpCode::Return)]);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_constant_out_of_range()
+{
+    let bytes = raw_file(&[u8::from(OpCode::Constant), 0, u8::from(OpCode::Return)]);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_missing_operands()
+{
+    let bytes = raw_file(&[u8::from(OpCode::Return), u8::from(OpCode::Jump), 0]);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_jump_out_of_range()
+{
+    let bytes = raw_file(&[u8::from(OpCode::Jump), 0, 10, u8::from(OpCode::Return)]);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_chunk_without_return()
+{
+    let bytes = raw_file(&[u8::from(OpCode::Nil), u8::from(OpCode::Pop)]);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_line_table_not_covering_the_code()
+{
+    let mut bytes = raw_file(&[u8::from(OpCode::Nil), u8::from(OpCode::Return)]);
+    // Move the only run so it starts after the first instruction
+    let run = 6 + 4 + 2 + 4;
+    bytes[run..run + 4].copy_from_slice(&1u32.to_le_bytes());
+
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+#[test]
+fn rejects_unknown_constant_tag()
+{
+    let mut bytes = raw_file(&[u8::from(OpCode::Return)]);
+    let count = bytes.len() - 4;
+    bytes[count..].copy_from_slice(&1u32.to_le_bytes());
+    bytes.push(99);
+
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))));
+}
+
+fn rejects(code: &[u8])
+{
+    let bytes = raw_file(code);
+    assert!(matches!(load(&mut VM::new(), &bytes), Err(ChunkError::Malformed(_))), "accepted {:?}", code);
+}
+
+#[test]
+fn rejects_jump_to_the_end_of_the_code()
+{
+    rejects(&[u8::from(OpCode::Nil), u8::from(OpCode::Jump), 0, 1, u8::from(OpCode::Return)]);
+}
+
+#[test]
+fn rejects_jump_into_an_operand()
+{
+    // Lands on the slot of GetLocal rather than the instruction
+    rejects(&[u8::from(OpCode::Jump), 0, 1, u8::from(OpCode::GetLocal), 0, u8::from(OpCode::Return)]);
+    rejects(&[u8::from(OpCode::Nil), u8::from(OpCode::Loop), 0, 5, u8::from(OpCode::Return)]);
+}
+
+#[test]
+fn rejects_local_slot_out_of_range()
+{
+    // The script's frame only holds the script itself in slot 0
+    rejects(&[u8::from(OpCode::GetLocal), 1, u8::from(OpCode::Return)]);
+    rejects(&[u8::from(OpCode::Nil), u8::from(OpCode::SetLocal), 2, u8::from(OpCode::Return)]);
+}
+
+#[test]
+fn rejects_upvalue_out_of_range()
+{
+    rejects(&[u8::from(OpCode::GetUpvalue), 0, u8::from(OpCode::Return)]);
+    rejects(&[u8::from(OpCode::Nil), u8::from(OpCode::SetUpvalue), 0, u8::from(OpCode::Return)]);
+}
+
+#[test]
+fn rejects_stack_underflow()
+{
+    rejects(&[u8::from(OpCode::Pop), u8::from(OpCode::Pop), u8::from(OpCode::Nil), u8::from(OpCode::Return)]);
+    rejects(&[u8::from(OpCode::Call), 3, u8::from(OpCode::Return)]);
+}
+
+#[test]
+fn rejects_paths_with_different_stack_heights()
+{
+    // The jump skips the second Nil, so Return is reached with two values or three
+    rejects(&[
+        u8::from(OpCode::Nil), u8::from(OpCode::JumpIfFalse), 0, 1,
+        u8::from(OpCode::Nil), u8::from(OpCode::Return),
+    ]);
+}
+
+#[test]
+fn rejects_captures_out_of_range()
+{
+    let source = "fn outer() { let x = 1; fn inner() { return x; } return inner; }";
+    let bytes = to_bytes(&compile(&mut VM::new(), source));
+
+    // inner captures outer's local slot 1
+    let closure = u8::from(OpCode::Closure);
+    let at = bytes.windows(4)
+        .position(|window| window[0] == closure && window[2] == 1 && window[3] == 1)
+        .expect("no closure capturing a local");
+
+    assert!(load(&mut VM::new(), &bytes).is_ok());
+
+    let mut slot = bytes.clone();
+    slot[at + 3] = 9;
+    assert!(matches!(load(&mut VM::new(), &slot), Err(ChunkError::Malformed(_))));
+
+    // Capturing an upvalue, when outer has none
+    let mut upvalue = bytes.clone();
+    upvalue[at + 2] = 0;
+    assert!(matches!(load(&mut VM::new(), &upvalue), Err(ChunkError::Malformed(_))));
+
+    let mut flag = bytes;
+    flag[at + 2] = 2;
+    assert!(matches!(load(&mut VM::new(), &flag), Err(ChunkError::Malformed(_))));
+}
+
+// Validation only counts values, so a hand written chunk can put the wrong
+// kind of value where the VM expects a class. That has to be a runtime error
+fn runtime_error(code: &[u8]) -> String
+{
+    // The one constant is the string "name"
+    let mut bytes = raw_file(code);
+    let count = bytes.len() - 4;
+    bytes[count..].copy_from_slice(&1u32.to_le_bytes());
+    bytes.push(3);
+    bytes.extend_from_slice(&4u32.to_le_bytes());
+    bytes.extend_from_slice(b"name");
+
+    let mut vm = VM::new();
+    let chunk = load(&mut vm, &bytes).unwrap();
+
+    match vm.interpret_chunk(chunk)
+    {
+        InterpretResult::RuntimeError(error) => error.message,
+        _ => panic!("expected a runtime error from {:?}", code),
+    }
+}
+
+#[test]
+fn crafted_chunks_without_a_class_are_runtime_errors()
+{
+    use OpCode::*;
+    let (nil, pop, ret) = (u8::from(Nil), u8::from(Pop), u8::from(Return));
+    let (constant, class) = (u8::from(Constant), u8::from(Class));
+
+    let superclass = "Superclass must be a class.";
+    assert_eq!(runtime_error(&[nil, nil, u8::from(GetSuper), 0, pop, nil, ret]), superclass);
+    assert_eq!(runtime_error(&[nil, constant, 0, u8::from(GetSuper), 0, pop, nil, ret]), superclass);
+    assert_eq!(runtime_error(&[nil, nil, u8::from(SuperInvoke), 0, 0, pop, nil, ret]), superclass);
+
+    assert_eq!(runtime_error(&[class, 0, constant, 0, u8::from(Inherit), pop, nil, ret]), "Subclass must be a class.");
+
+    let method = "Methods must be closures defined on a class.";
+    assert_eq!(runtime_error(&[constant, 0, constant, 0, u8::from(Method), 0, pop, nil, ret]), method);
+    assert_eq!(runtime_error(&[class, 0, constant, 0, u8::from(Method), 0, pop, nil, ret]), method);
+}
+
+// Anything the compiler produces has to pass validation
+#[test]
+fn every_test_script_round_trips()
+{
+    let mut directories = vec![std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("lox")];
+
+    while let Some(directory) = directories.pop()
+    {
+        for entry in std::fs::read_dir(directory).unwrap()
+        {
+            let path = entry.unwrap().path();
+
+            if path.is_dir()
+            {
+                directories.push(path);
+                continue;
+            }
+
+            let source = std::fs::read_to_string(&path).unwrap();
+            let mut vm = VM::new();
+
+            if let Ok(chunk) = vm.compile(source)
+            {
+                let bytes = to_bytes(&chunk);
+                assert!(load(&mut vm, &bytes).is_ok(), "{} failed to load", path.display());
+            }
+        }
+    }
+}
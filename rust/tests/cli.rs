@@ -0,0 +1,164 @@
+use std::{
+    env,
+    fs,
+    io::Write,
+    path::PathBuf,
+    process::{Command, Output, Stdio},
+};
+
+const ENGINES: &[&str] = &["bytecode", "ast"];
+
+// Writes the script somewhere private to the test, so tests can run in parallel
+fn script(name: &str, source: &str) -> PathBuf
+{
+    let path = env::temp_dir().join(format!("rlox_cli_{}_{}.lox", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+fn rlox(args: &[&str]) -> Output
+{
+    Command::new(env!("CARGO_BIN_EXE_rlox")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String
+{
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn scripts_see_their_arguments()
+{
+    let path = script("args", "let i = 0;\nwhile (i < argc()) { print argv(i); i = i + 1; }");
+    let path = path.to_str().unwrap();
+
+    for engine in ENGINES
+    {
+        let output = rlox(&["--engine", engine, "run", path, "one", "--two"]);
+
+        assert_eq!(output.status.code(), Some(0), "{}", engine);
+        assert_eq!(stdout(&output), format!("{}\none\n--two\n", path), "{}", engine);
+    }
+}
+
+#[test]
+fn exit_codes()
+{
+    let compile_error = script("compile_error", "print 1 +;");
+    let runtime_error = script("runtime_error", "print 1;\nprint -\"a\";");
+    let out_of_range = script("out_of_range", "print argv(3);");
+
+    for engine in ENGINES
+    {
+        let run = |path: &PathBuf| rlox(&["run", "--engine", engine, path.to_str().unwrap()]).status.code();
+
+        assert_eq!(run(&compile_error), Some(65), "{}", engine);
+        assert_eq!(run(&runtime_error), Some(70), "{}", engine);
+        assert_eq!(run(&out_of_range), Some(70), "{}", engine);
+
+        let check = rlox(&["check", "--engine", engine, compile_error.to_str().unwrap()]);
+        assert_eq!(check.status.code(), Some(65), "{}", engine);
+    }
+
+    assert_eq!(rlox(&["run", "no_such_script.lox"]).status.code(), Some(74));
+    assert_eq!(rlox(&["disasm", "--engine", "ast", compile_error.to_str().unwrap()]).status.code(), Some(64));
+}
+
+// Warnings and errors go to stderr, so stdout is only what the script printed
+#[test]
+fn diagnostics_stay_out_of_stdout()
+{
+    let path = script("diagnostics", "fn f() { let unused = 1; }\nprint \"out\";\nprint -\"a\";");
+
+    for engine in ENGINES
+    {
+        let output = rlox(&["--engine", engine, "run", path.to_str().unwrap()]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        assert_eq!(stdout(&output), "out\n", "{}", engine);
+        assert!(stderr.contains("Operand must be a number."), "{}: {}", engine, stderr);
+    }
+
+    let output = rlox(&["--engine", "ast", "run", path.to_str().unwrap()]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("never used"));
+
+    let broken = script("diagnostics_broken", "print 1 +;");
+    let output = rlox(&["--engine", "ast", "run", broken.to_str().unwrap()]);
+    assert_eq!(stdout(&output), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Expect expression."));
+}
+
+#[test]
+fn compiled_files_run_the_same()
+{
+    let path = script("compiled", "fn sq(x) { return x * x; }\nprint sq(argc());");
+    let compiled = path.with_extension("loxc");
+
+    let compile = rlox(&["compile", path.to_str().unwrap(), "-o", compiled.to_str().unwrap()]);
+    assert_eq!(compile.status.code(), Some(0));
+
+    let from_source = rlox(&["run", path.to_str().unwrap(), "a"]);
+    let from_bytecode = rlox(&["run", compiled.to_str().unwrap(), "a"]);
+
+    assert_eq!(stdout(&from_source), "4\n");
+    assert_eq!(stdout(&from_bytecode), "4\n");
+}
+
+#[test]
+fn tokens_include_comments()
+{
+    let path = script("tokens", "let a; // note");
+    let output = rlox(&["tokens", path.to_str().unwrap()]);
+
+    let types: Vec<String> = stdout(&output).lines()
+        .map(|line| line.split_whitespace().nth(1).unwrap().to_string())
+        .collect();
+
+    assert_eq!(types, vec!["Var", "Identifier", "Semicolon", "Comment", "EOF"]);
+}
+
+#[test]
+fn fmt_prints_and_checks()
+{
+    let messy = script("fmt_messy", "fn f(a){return a;} // done");
+    let output = rlox(&["fmt", messy.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "fn f(a)\n{\n    return a;\n} // done\n");
+
+    let check = rlox(&["fmt", "--check", messy.to_str().unwrap()]);
+    assert_eq!(check.status.code(), Some(1));
+    assert_eq!(stdout(&check), "");
+
+    let tidy = script("fmt_tidy", &stdout(&output));
+    assert_eq!(rlox(&["fmt", "--check", tidy.to_str().unwrap()]).status.code(), Some(0));
+
+    let broken = script("fmt_broken", "fn (");
+    assert_eq!(rlox(&["fmt", broken.to_str().unwrap()]).status.code(), Some(65));
+}
+
+// Piped input still goes through the line editor, a line at a time
+#[test]
+fn repl_waits_for_unfinished_input()
+{
+    let home = env::temp_dir().join(format!("rlox_cli_{}_home", std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+
+    let mut repl = Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let input = "print \"a\nb\";\n{ print 1;\nprint 2; }\nprint \"\\q\";\nprint 1 +;\nprint 3;\n";
+    repl.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+
+    let output = repl.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(stdout(&output).ends_with("a\nb\n1\n2\n3\n"), "{}", stdout(&output));
+    assert!(stderr.contains("Invalid escape sequence"), "{}", stderr);
+    assert!(stderr.contains("Expect expression."), "{}", stderr);
+}
@@ -0,0 +1,53 @@
+// Shared by the integration tests, each of which only uses some of it
+#![allow(dead_code)]
+
+use std::{
+    cell::RefCell,
+    io::{self, Write},
+    rc::Rc,
+};
+
+// Collects everything a script prints, shared with the interpreter writing to it
+#[derive(Clone, Default)]
+pub struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+impl Capture
+{
+    pub fn lines(&self) -> Vec<String>
+    {
+        String::from_utf8_lossy(&self.0.borrow()).lines().map(|line| line.to_string()).collect()
+    }
+}
+
+// Small xorshift generator so generated inputs are random looking but reproducible
+pub struct Rng(pub u64);
+
+impl Rng
+{
+    pub fn next(&mut self) -> u64
+    {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str
+    {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
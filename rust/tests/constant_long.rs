@@ -0,0 +1,156 @@
+use one_hundred_days_of_code::bytecode::{
+    chunk::Chunk,
+    vm::{InterpretResult, VM},
+};
+
+// Sums count literals into a global and fails at runtime if the total is
+// off. The error is raised by negating a string.
+fn sum_program(count: usize) -> String
+{
+    let literals: Vec<String> = (1..=count).map(|n| n.to_string()).collect();
+    let expected = count * (count + 1) / 2;
+
+    format!("let total = {}; if (total != {}) {{ -\"wrong total\"; }}", literals.join(" + "), expected)
+}
+
+fn run(source: String) -> InterpretResult
+{
+    VM::new().interpret(source)
+}
+
+// Compiles, writes the chunk out and runs what reads back in
+fn run_serialized(source: String) -> InterpretResult
+{
+    let mut vm = VM::new();
+    let chunk = match vm.compile(source)
+    {
+        Ok(chunk) => chunk,
+        Err(errors) => panic!("failed to compile: {:?}", errors),
+    };
+
+    let mut bytes = Vec::new();
+    chunk.write_to(&mut bytes).unwrap();
+
+    let mut vm = VM::new();
+    let chunk = Chunk::read_from(&mut bytes.as_slice(), &mut vm).unwrap();
+    vm.interpret_chunk(chunk)
+}
+
+// An expression statement using up count constants
+fn filler(count: usize) -> String
+{
+    let literals: Vec<String> = (1..=count).map(|n| n.to_string()).collect();
+    format!("{};\n", literals.join(" + "))
+}
+
+#[test]
+fn thousands_of_literals()
+{
+    assert!(matches!(run(sum_program(5000)), InterpretResult::Okay));
+}
+
+#[test]
+fn literals_around_the_short_operand_limit()
+{
+    for count in [255, 256, 257, 258]
+    {
+        assert!(matches!(run(sum_program(count)), InterpretResult::Okay), "failed with {} literals", count);
+    }
+}
+
+#[test]
+fn wrong_total_is_detected()
+{
+    let source = sum_program(300).replace("!= 45150", "!= 45151");
+    assert!(matches!(run(source), InterpretResult::RuntimeError(_)));
+}
+
+#[test]
+fn strings_past_the_short_operand_limit()
+{
+    let mut source = String::from("let s = \"\";");
+    for n in 0..400
+    {
+        source.push_str(&format!(" s = s + \"{}\";", n % 10));
+    }
+
+    let expected: String = (0..400).map(|n| (n % 10).to_string()).collect();
+    source.push_str(&format!(" if (s != \"{}\") {{ -\"wrong string\"; }}", expected));
+
+    assert!(matches!(run(source), InterpretResult::Okay));
+}
+
+// Each name is stored once however often it's mentioned
+#[test]
+fn repeated_names_share_a_constant()
+{
+    let mut source = String::from("let x = 0;\nlet i = 1;\n");
+    for _ in 0..300
+    {
+        source.push_str("x = x + i;\n");
+    }
+    source.push_str("if (x != 300) { -\"wrong total\"; }");
+
+    assert!(matches!(run(source), InterpretResult::Okay));
+}
+
+#[test]
+fn long_constants_survive_serialization()
+{
+    assert!(matches!(run_serialized(sum_program(1000)), InterpretResult::Okay));
+}
+
+#[test]
+fn new_global_after_the_short_operand_limit()
+{
+    let source = filler(300) + "let later = 5;\nlater = later + 1;\nif (later != 6) { -\"wrong value\"; }";
+
+    assert!(matches!(run(source.clone()), InterpretResult::Okay));
+    assert!(matches!(run_serialized(source), InterpretResult::Okay));
+}
+
+// Every instruction taking a name or function constant, each first used past the limit
+#[test]
+fn names_and_functions_after_the_short_operand_limit()
+{
+    let filler = filler(300);
+    let source = format!("{filler}
+        class Base {{ greet() {{ return \"base\"; }} }}
+        class Derived < Base
+        {{
+            init() {{ {filler} this.field = 1; }}
+            greet() {{ {filler} return parent.greet(); }}
+            bound() {{ {filler} return parent.greet; }}
+        }}
+
+        fn counter() {{ let n = 0; fn next() {{ n = n + 1; return n; }} return next; }}
+
+        let d = Derived();
+        d.field = d.field + 1;
+        let next = counter();
+        next();
+
+        if (d.greet() != \"base\" or d.bound()() != \"base\" or d.field != 2 or next() != 2)
+        {{
+            -\"wrong result\";
+        }}");
+
+    assert!(matches!(run(source.clone()), InterpretResult::Okay));
+    assert!(matches!(run_serialized(source), InterpretResult::Okay));
+}
+
+#[test]
+fn hundreds_of_distinct_globals()
+{
+    let mut source = String::new();
+    for n in 0..300
+    {
+        source.push_str(&format!("let g{} = {};\n", n, n));
+    }
+
+    let globals: Vec<String> = (0..300).map(|n| format!("g{}", n)).collect();
+    source.push_str(&format!("if ({} != 44850) {{ -\"wrong total\"; }}", globals.join(" + ")));
+
+    assert!(matches!(run(source.clone()), InterpretResult::Okay));
+    assert!(matches!(run_serialized(source), InterpretResult::Okay));
+}
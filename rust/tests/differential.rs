@@ -0,0 +1,245 @@
+/*
+    Runs every script under tests/lox through both interpreters and checks
+    them against the script's annotations and against each other.
+
+        print 1 + 2; // expect: 3
+        print -"a";  // expect runtime error: Operand must be a number.
+
+    A runtime error is expected on the line its annotation is written on.
+    Run with --nocapture to see the report even when everything passes.
+
+    Scripts in KNOWN_DIVERGENCES are reported but don't fail the test, until
+    they start agreeing and need taking off the list.
+*/
+
+mod common;
+
+use std::{
+    fmt,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use common::Capture;
+use one_hundred_days_of_code::{
+    ast::{
+        error::ErrorHandler,
+        interpreter::Interpreter,
+        parser::Parser,
+        resolver::Resolver,
+        scanner::Scanner,
+    },
+    bytecode::vm::{InterpretResult, VM},
+};
+
+#[derive(Debug, PartialEq)]
+enum Failure
+{
+    Compile,
+    Runtime { message: String, line: usize },
+}
+
+impl fmt::Display for Failure
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self
+        {
+            Failure::Compile => write!(f, "a compile error"),
+            Failure::Runtime { message, line } => write!(f, "runtime error on line {}: {}", line, message),
+        }
+    }
+}
+
+// What a script printed and how it stopped
+#[derive(Debug, PartialEq)]
+struct Outcome
+{
+    output: Vec<String>,
+    failure: Option<Failure>,
+}
+
+// Script and why it diverges
+const KNOWN_DIVERGENCES: &[(&str, &str)] = &[];
+
+const EXPECT: &str = "// expect: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+
+fn expected(source: &str) -> Outcome
+{
+    let mut output = Vec::new();
+    let mut failure = None;
+
+    for (i, line) in source.lines().enumerate()
+    {
+        if let Some(start) = line.find(EXPECT)
+        {
+            output.push(line[start + EXPECT.len()..].to_string());
+        }
+        else if let Some(start) = line.find(EXPECT_RUNTIME_ERROR)
+        {
+            let message = line[start + EXPECT_RUNTIME_ERROR.len()..].to_string();
+            failure = Some(Failure::Runtime { message, line: i + 1 });
+        }
+    }
+
+    Outcome { output, failure }
+}
+
+fn run_bytecode(source: &str) -> Outcome
+{
+    let capture = Capture::default();
+
+    let mut vm = VM::new();
+    vm.set_output(Box::new(capture.clone()));
+
+    let failure = match vm.interpret(source.to_string())
+    {
+        InterpretResult::Okay => None,
+        InterpretResult::CompilerError(_) => Some(Failure::Compile),
+        InterpretResult::RuntimeError(error) => Some(Failure::Runtime { message: error.message, line: error.line }),
+    };
+
+    vm.free();
+    Outcome { output: capture.lines(), failure }
+}
+
+fn run_ast(source: &str) -> Outcome
+{
+    let compile_error = Outcome { output: Vec::new(), failure: Some(Failure::Compile) };
+
+    let mut scanner = Scanner::with_source(source.to_string());
+    let tokens = match scanner.scan_tokens()
+    {
+        Ok(tokens) => tokens.clone(),
+        Err(_) => return compile_error,
+    };
+
+    let mut errors = ErrorHandler::new();
+    let statements = Parser::new(tokens, &mut errors).parse();
+
+    if !errors.had_error()
+    {
+        Resolver::new(&mut errors).resolve(&statements);
+    }
+
+    if errors.had_error()
+    {
+        return compile_error;
+    }
+
+    let capture = Capture::default();
+
+    let mut interpreter = Interpreter::new();
+    interpreter.set_output(Box::new(capture.clone()));
+    interpreter.interpret(&statements, &mut errors);
+
+    let failure = errors.last_runtime_error().map(|error| Failure::Runtime
+    {
+        message: error.message.clone(),
+        line: error.token.line as usize,
+    });
+
+    Outcome { output: capture.lines(), failure }
+}
+
+// Describes the first way actual strays from expected, or None if it doesn't
+fn compare(expected: &Outcome, actual: &Outcome) -> Option<String>
+{
+    for (i, line) in expected.output.iter().enumerate()
+    {
+        match actual.output.get(i)
+        {
+            Some(got) if got == line => {},
+            Some(got) => return Some(format!("output line {}: expected {:?}, got {:?}", i + 1, line, got)),
+            None => return Some(format!("output line {}: expected {:?}, got nothing", i + 1, line)),
+        }
+    }
+
+    if let Some(extra) = actual.output.get(expected.output.len())
+    {
+        return Some(format!("output line {}: unexpected {:?}", expected.output.len() + 1, extra));
+    }
+
+    match (&expected.failure, &actual.failure)
+    {
+        (a, b) if a == b => None,
+        (Some(a), Some(b)) => Some(format!("expected {}, got {}", a, b)),
+        (Some(a), None) => Some(format!("expected {}, but it ran to the end", a)),
+        (None, Some(b)) => Some(format!("unexpected {}", b)),
+        (None, None) => None,
+    }
+}
+
+fn scripts(directory: &Path, found: &mut Vec<PathBuf>)
+{
+    for entry in fs::read_dir(directory).unwrap()
+    {
+        let path = entry.unwrap().path();
+
+        if path.is_dir()
+        {
+            scripts(&path, found);
+        }
+        else if path.extension().is_some_and(|extension| extension == "lox")
+        {
+            found.push(path);
+        }
+    }
+}
+
+#[test]
+fn ast_and_bytecode_agree()
+{
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("lox");
+
+    let mut paths = Vec::new();
+    scripts(&root, &mut paths);
+    paths.sort();
+
+    assert!(!paths.is_empty(), "no scripts found in {}", root.display());
+
+    let mut report = Vec::new();
+    let mut known = Vec::new();
+    let mut fixed = Vec::new();
+
+    for path in &paths
+    {
+        let source = fs::read_to_string(path).unwrap();
+        let name = path.strip_prefix(&root).unwrap().display().to_string();
+
+        let expected = expected(&source);
+        let bytecode = run_bytecode(&source);
+        let ast = run_ast(&source);
+
+        let divergences: Vec<String> = vec![
+                ("bytecode", compare(&expected, &bytecode)),
+                ("ast", compare(&expected, &ast)),
+                ("bytecode vs ast", compare(&bytecode, &ast)),
+            ]
+            .into_iter()
+            .filter_map(|(label, divergence)| divergence.map(|divergence| format!("    {}: {}", label, divergence)))
+            .collect();
+
+        let reason = KNOWN_DIVERGENCES.iter()
+            .find(|(script, _)| name.replace('\\', "/") == *script)
+            .map(|(_, reason)| reason);
+
+        match (reason, divergences.is_empty())
+        {
+            (Some(reason), false) => known.push(format!("{} (known, {})\n{}", name, reason, divergences.join("\n"))),
+            (Some(_), true) => fixed.push(name),
+            (None, false) => report.push(format!("{}\n{}", name, divergences.join("\n"))),
+            (None, true) => {},
+        }
+    }
+
+    println!("=== {} scripts, {} diverged, {} known ===", paths.len(), report.len(), known.len());
+    for entry in report.iter().chain(&known)
+    {
+        println!("{}", entry);
+    }
+
+    assert!(report.is_empty(), "{} of {} scripts diverged:\n{}", report.len(), paths.len(), report.join("\n"));
+    assert!(fixed.is_empty(), "no longer diverging, take these off KNOWN_DIVERGENCES: {:?}", fixed);
+}
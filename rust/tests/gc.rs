@@ -0,0 +1,38 @@
+/*
+    Checks the collector's byte count, which paces when it runs.
+
+    To run every test with a collection on each allocation:
+
+        RUSTFLAGS='--cfg stress_gc="true"' cargo test
+*/
+
+use one_hundred_days_of_code::bytecode::vm::{InterpretResult, VM};
+
+// Grows a class's methods and an instance's fields well past their
+// starting capacity, all in a block so it's garbage once the run ends
+fn growing_objects() -> String
+{
+    let methods: String = (0..50).map(|n| format!("m{}() {{ return {}; }} ", n, n)).collect();
+    let fields: String = (0..50).map(|n| format!("a.f{} = {}; ", n, n)).collect();
+
+    format!("{{ class A {{ {} }} let a = A(); {} }}", methods, fields)
+}
+
+#[test]
+fn freeing_garbage_gives_back_what_it_took()
+{
+    let mut vm = VM::new();
+
+    assert!(matches!(vm.interpret(String::new()), InterpretResult::Okay));
+    vm.collect_garbage();
+    let before = vm.bytes_allocated();
+
+    for _ in 0..3
+    {
+        assert!(matches!(vm.interpret(growing_objects()), InterpretResult::Okay));
+        vm.collect_garbage();
+        assert_eq!(vm.bytes_allocated(), before);
+    }
+
+    vm.free();
+}
@@ -0,0 +1,287 @@
+use one_hundred_days_of_code::{
+    ast,
+    lexer::{
+        keywords::keyword,
+        scanner::{unescape, Scanner},
+        token::TokenType,
+    },
+};
+
+// Every token up to and including EOF, as "Type 'lexeme' line:column",
+// with the message in place of the lexeme for errors
+fn stream(source: &str) -> Vec<String>
+{
+    let mut scanner = Scanner::new();
+    scanner.init(source.to_string());
+
+    let mut rendered = Vec::new();
+
+    loop
+    {
+        let token = scanner.scan_token();
+
+        let text = match token.type_of
+        {
+            TokenType::Error => scanner.error_message().to_string(),
+            _ => scanner.lexeme(&token).to_string(),
+        };
+
+        rendered.push(format!("{:?} {:?} {}:{}", token.type_of, text, token.line, token.column));
+
+        if token.type_of == TokenType::EOF
+        {
+            return rendered;
+        }
+    }
+}
+
+#[test]
+fn declarations()
+{
+    assert_eq!(stream("let a = 1.5;\nfn f(x) { return x; }"), vec![
+        "Var \"let\" 1:1",
+        "Identifier \"a\" 1:5",
+        "Equal \"=\" 1:7",
+        "Number \"1.5\" 1:9",
+        "Semicolon \";\" 1:12",
+        "Func \"fn\" 2:1",
+        "Identifier \"f\" 2:4",
+        "LeftParen \"(\" 2:5",
+        "Identifier \"x\" 2:6",
+        "RightParen \")\" 2:7",
+        "LeftBrace \"{\" 2:9",
+        "Return \"return\" 2:11",
+        "Identifier \"x\" 2:18",
+        "Semicolon \";\" 2:19",
+        "RightBrace \"}\" 2:21",
+        "EOF \"\" 2:22",
+    ]);
+}
+
+#[test]
+fn classes()
+{
+    assert_eq!(stream("class B < A { m() { parent.m(this); } }"), vec![
+        "Class \"class\" 1:1",
+        "Identifier \"B\" 1:7",
+        "Less \"<\" 1:9",
+        "Identifier \"A\" 1:11",
+        "LeftBrace \"{\" 1:13",
+        "Identifier \"m\" 1:15",
+        "LeftParen \"(\" 1:16",
+        "RightParen \")\" 1:17",
+        "LeftBrace \"{\" 1:19",
+        "Super \"parent\" 1:21",
+        "Dot \".\" 1:27",
+        "Identifier \"m\" 1:28",
+        "LeftParen \"(\" 1:29",
+        "This \"this\" 1:30",
+        "RightParen \")\" 1:34",
+        "Semicolon \";\" 1:35",
+        "RightBrace \"}\" 1:37",
+        "RightBrace \"}\" 1:39",
+        "EOF \"\" 1:40",
+    ]);
+}
+
+#[test]
+fn operators()
+{
+    assert_eq!(stream("!a != b == c <= d >= e < f > g = -h + i * j / k"), vec![
+        "Bang \"!\" 1:1",
+        "Identifier \"a\" 1:2",
+        "BangEqual \"!=\" 1:4",
+        "Identifier \"b\" 1:7",
+        "EqualEqual \"==\" 1:9",
+        "Identifier \"c\" 1:12",
+        "LessEqual \"<=\" 1:14",
+        "Identifier \"d\" 1:17",
+        "GreaterEqual \">=\" 1:19",
+        "Identifier \"e\" 1:22",
+        "Less \"<\" 1:24",
+        "Identifier \"f\" 1:26",
+        "Greater \">\" 1:28",
+        "Identifier \"g\" 1:30",
+        "Equal \"=\" 1:32",
+        "Minus \"-\" 1:34",
+        "Identifier \"h\" 1:35",
+        "Plus \"+\" 1:37",
+        "Identifier \"i\" 1:39",
+        "Star \"*\" 1:41",
+        "Identifier \"j\" 1:43",
+        "Slash \"/\" 1:45",
+        "Identifier \"k\" 1:47",
+        "EOF \"\" 1:48",
+    ]);
+}
+
+#[test]
+fn comments_are_trivia_tokens()
+{
+    assert_eq!(stream("a // one\n/// two\n/=b//"), vec![
+        "Identifier \"a\" 1:1",
+        "Comment \"// one\" 1:3",
+        "Comment \"/// two\" 2:1",
+        "Slash \"/\" 3:1",
+        "Equal \"=\" 3:2",
+        "Identifier \"b\" 3:3",
+        "Comment \"//\" 3:4",
+        "EOF \"\" 3:6",
+    ]);
+}
+
+#[test]
+fn strings_keep_their_escapes()
+{
+    assert_eq!(stream("\"a\\\"b\" \"東\\u{4eac}\" \"two\nlines\" x"), vec![
+        "String \"\\\"a\\\\\\\"b\\\"\" 1:1",
+        "String \"\\\"東\\\\u{4eac}\\\"\" 1:8",
+        "String \"\\\"two\\nlines\\\"\" 1:20",
+        "Identifier \"x\" 2:8",
+        "EOF \"\" 2:9",
+    ]);
+}
+
+#[test]
+fn numbers_and_dots()
+{
+    assert_eq!(stream("12 3.25 4. .5 a.b"), vec![
+        "Number \"12\" 1:1",
+        "Number \"3.25\" 1:4",
+        "Number \"4\" 1:9",
+        "Dot \".\" 1:10",
+        "Dot \".\" 1:12",
+        "Number \"5\" 1:13",
+        "Identifier \"a\" 1:15",
+        "Dot \".\" 1:16",
+        "Identifier \"b\" 1:17",
+        "EOF \"\" 1:18",
+    ]);
+}
+
+#[test]
+fn unicode_identifiers_and_columns()
+{
+    assert_eq!(stream("let naïve = 東京;\n\tπ_2"), vec![
+        "Var \"let\" 1:1",
+        "Identifier \"naïve\" 1:5",
+        "Equal \"=\" 1:11",
+        "Identifier \"東京\" 1:13",
+        "Semicolon \";\" 1:15",
+        "Identifier \"π_2\" 2:2",
+        "EOF \"\" 2:5",
+    ]);
+}
+
+#[test]
+fn errors_are_tokens_and_scanning_carries_on()
+{
+    assert_eq!(stream("a # 🌍 \"\\q\" b \"open"), vec![
+        "Identifier \"a\" 1:1",
+        "Error \"Unexpected character '#'.\" 1:3",
+        "Error \"Unexpected character '🌍'.\" 1:5",
+        "Error \"Invalid escape sequence '\\\\q'.\" 1:7",
+        "Identifier \"b\" 1:12",
+        "Error \"Unterminated string.\" 1:14",
+        "EOF \"\" 1:19",
+    ]);
+}
+
+// Only an error that more input could fix, so the REPL knows to wait for it
+#[test]
+fn open_strings_are_incomplete()
+{
+    let error = |source: &str|
+    {
+        let mut scanner = Scanner::new();
+        scanner.init(source.to_string());
+
+        let token = scanner.scan_token();
+        assert_eq!(token.type_of, TokenType::Error, "{}", source);
+        scanner.error_is_incomplete()
+    };
+
+    assert!(error("\"open"));
+    assert!(error("\"line\nafter line"));
+    assert!(!error("#"));
+    assert!(!error("\"\\q\""));
+}
+
+#[test]
+fn eof_repeats()
+{
+    let mut scanner = Scanner::new();
+    scanner.init("x".to_string());
+
+    assert_eq!(scanner.scan_token().type_of, TokenType::Identifier);
+    for _ in 0..3
+    {
+        assert_eq!(scanner.scan_token().type_of, TokenType::EOF);
+    }
+}
+
+#[test]
+fn one_keyword_table()
+{
+    use TokenType::*;
+
+    let keywords = [
+        ("and", And), ("class", Class), ("else", Else), ("false", False), ("for", For), ("fn", Func),
+        ("if", If), ("null", Null), ("or", Or), ("print", Print), ("return", Return), ("parent", Super),
+        ("this", This), ("true", True), ("let", Var), ("while", While),
+    ];
+
+    for (text, type_of) in keywords.iter()
+    {
+        assert_eq!(keyword(text), Some(*type_of));
+        assert_eq!(stream(text)[0], format!("{:?} {:?} 1:1", type_of, text));
+    }
+
+    // Close to a keyword is still an identifier
+    for text in ["an", "classy", "f", "fo", "fun", "fnord", "nil", "var", "super", "parents", "thi", "lets", "_if", "If"].iter()
+    {
+        assert_eq!(keyword(text), None, "{}", text);
+        assert!(stream(text)[0].starts_with("Identifier "), "{}", text);
+    }
+}
+
+#[test]
+fn unescaping()
+{
+    assert_eq!(unescape("a\\nb\\tc\\\"d\\\\e\\r\\0"), Ok("a\nb\tc\"d\\e\r\0".to_string()));
+    assert_eq!(unescape("\\u{41}\\u{e9}\\u{1F600}"), Ok("Aé😀".to_string()));
+    assert_eq!(unescape("plain 東京"), Ok("plain 東京".to_string()));
+
+    for bad in ["\\q", "\\u41", "\\u{}", "\\u{1234567}", "\\u{D800}", "\\u{41", "\\"].iter()
+    {
+        assert!(unescape(bad).is_err(), "accepted {}", bad);
+    }
+}
+
+// The ast front end is a thin layer over the lexer, so both see the same tokens
+#[test]
+fn ast_scanner_matches_the_lexer()
+{
+    let corpus = [
+        "let a = 1.5;\nfn f(x) { return x; }",
+        "class B < A { m() { parent.m(this); } } // done",
+        "print \"東\\u{4eac}\" + \"two\nlines\" != null or false and true;",
+        "for (let i = 0; i <= 10; i = i + 1) while (!x) if (y >= 2) {} else print -z / 2 * 3;",
+    ];
+
+    for source in corpus.iter()
+    {
+        let mut scanner = ast::scanner::Scanner::with_source(source.to_string());
+        let tokens = scanner.scan_tokens().unwrap().clone();
+
+        let from_ast: Vec<String> = tokens.iter()
+            .map(|token| format!("{:?} {:?} {}:{}", token.type_of, token.lexeme, token.line, token.column))
+            .collect();
+
+        let from_lexer: Vec<String> = stream(source).into_iter()
+            .filter(|token| !token.starts_with("Comment "))
+            .collect();
+
+        assert_eq!(from_ast, from_lexer, "{:?}", source);
+    }
+}
@@ -0,0 +1,42 @@
+mod common;
+
+use common::Capture;
+use one_hundred_days_of_code::bytecode::vm::{InterpretResult, VM};
+
+// A closure that escaped before the error still sees what it captured,
+// once the stack it pointed into has been reset by the next run
+#[test]
+fn captured_variables_outlive_a_runtime_error()
+{
+    let capture = Capture::default();
+
+    let mut vm = VM::new();
+    vm.set_output(Box::new(capture.clone()));
+
+    let source = "let g; fn outer() { let x = \"c\"; fn inner() { return x; } g = inner; -\"boom\"; } outer();";
+    match vm.interpret(source.to_string())
+    {
+        InterpretResult::RuntimeError(error) => assert_eq!(error.message, "Operand must be a number."),
+        _ => panic!("expected a runtime error"),
+    }
+
+    assert!(matches!(vm.interpret("print g();".to_string()), InterpretResult::Okay));
+    assert_eq!(capture.lines(), vec!["c"]);
+
+    vm.free();
+}
+
+#[test]
+fn the_stack_is_kept_until_the_next_run()
+{
+    let mut vm = VM::new();
+    vm.set_output(Box::new(Capture::default()));
+
+    assert!(matches!(vm.interpret("let a = 1; { let b = 2; -\"x\"; }".to_string()), InterpretResult::RuntimeError(_)));
+    assert!(!vm.stack().is_empty());
+
+    assert!(matches!(vm.interpret("print a;".to_string()), InterpretResult::Okay));
+    assert!(vm.stack().is_empty());
+
+    vm.free();
+}